@@ -0,0 +1,385 @@
+use crate::chat_message::{ChatMessage, ToolCall};
+use crate::conversation::Conversation;
+use crate::llm_client::{LlmClient, LlmClientError, LlmResponse};
+use crate::tool::{Tool, ToolRegistry};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use log::debug;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+const DEFAULT_MAX_STEPS: usize = 10;
+
+/// Runs one tool call. An `Err` is reported back to the model as the tool result.
+pub type ToolHandler =
+    Arc<dyn Fn(Value) -> BoxFuture<'static, Result<String, String>> + Send + Sync>;
+
+pub type StepHook = Box<dyn Fn(&AgentStep) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct ToolResult {
+    pub call: ToolCall,
+    pub output: String,
+    pub is_error: bool,
+}
+
+/// One model call and the tool calls it led to.
+#[derive(Debug, Clone)]
+pub struct AgentStep {
+    pub step: usize,
+    pub response: LlmResponse,
+    pub tool_results: Vec<ToolResult>,
+}
+
+/// Keeps calling the model and running the tools it asks for until it gives
+/// a final answer or `max_steps` model calls have been made.
+pub struct Agent {
+    client: Arc<dyn LlmClient>,
+    model: String,
+    conversation: Conversation,
+    tools: ToolRegistry,
+    handlers: HashMap<String, ToolHandler>,
+    max_steps: usize,
+    hooks: Vec<StepHook>,
+}
+
+impl Agent {
+    pub fn new(client: Arc<dyn LlmClient>, model: &str) -> Self {
+        Self {
+            client,
+            model: model.to_string(),
+            conversation: Conversation::new(),
+            tools: ToolRegistry::new(),
+            handlers: HashMap::new(),
+            max_steps: DEFAULT_MAX_STEPS,
+            hooks: Vec::new(),
+        }
+    }
+
+    pub fn with_system_prompt(mut self, system_prompt: &str) -> Self {
+        self.conversation.push_system(system_prompt);
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn register_tool<F, Fut>(&mut self, tool: Tool, handler: F) -> Result<(), LlmClientError>
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, String>> + Send + 'static,
+    {
+        let name = tool.name.clone();
+        self.tools.register(tool)?;
+        self.handlers
+            .insert(name, Arc::new(move |arguments| handler(arguments).boxed()));
+        Ok(())
+    }
+
+    pub fn on_step<F>(&mut self, hook: F)
+    where
+        F: Fn(&AgentStep) + Send + Sync + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub fn conversation(&self) -> &Conversation {
+        &self.conversation
+    }
+
+    pub async fn run(&mut self, prompt: &str) -> Result<LlmResponse, LlmClientError> {
+        self.conversation.push_user(prompt);
+
+        for step in 1..=self.max_steps {
+            let response = self
+                .conversation
+                .send_with_tools(self.client.as_ref(), &self.model, self.tools.tools())
+                .await?;
+
+            let mut tool_results = Vec::with_capacity(response.tool_calls.len());
+            for call in &response.tool_calls {
+                let result = self.call_tool(call).await;
+                self.conversation
+                    .push(ChatMessage::tool(&call.id, &result.output).with_name(&call.name));
+                tool_results.push(result);
+            }
+
+            let done = tool_results.is_empty();
+            let agent_step = AgentStep {
+                step,
+                response,
+                tool_results,
+            };
+
+            for hook in &self.hooks {
+                hook(&agent_step);
+            }
+
+            if done {
+                return Ok(agent_step.response);
+            }
+        }
+
+        Err(LlmClientError::MaxStepsReached(self.max_steps))
+    }
+
+    async fn call_tool(&self, call: &ToolCall) -> ToolResult {
+        debug!("Calling tool {} ({})", call.name, call.id);
+
+        let outcome = match self.handlers.get(&call.name) {
+            Some(handler) => handler(call.arguments.clone()).await,
+            None => Err(format!("Unknown tool `{}`", call.name)),
+        };
+
+        match outcome {
+            Ok(output) => ToolResult {
+                call: call.clone(),
+                output,
+                is_error: false,
+            },
+            Err(error) => ToolResult {
+                call: call.clone(),
+                output: format!("Error: {error}"),
+                is_error: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_message::Role;
+    use crate::llm_client::{EmbedOptions, EmbedResponse, ModelInfo};
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Replies with scripted responses in order and records what it was sent.
+    #[derive(Default)]
+    struct MockClient {
+        replies: Mutex<VecDeque<LlmResponse>>,
+        requests: Mutex<Vec<Vec<ChatMessage>>>,
+    }
+
+    impl MockClient {
+        fn new(replies: Vec<LlmResponse>) -> Arc<Self> {
+            Arc::new(Self {
+                replies: Mutex::new(replies.into()),
+                ..Default::default()
+            })
+        }
+
+        fn requests(&self) -> Vec<Vec<ChatMessage>> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl LlmClient for MockClient {
+        async fn generate(
+            &self,
+            _model: &str,
+            _system_prompt: &str,
+            _prompt: &str,
+            _json: bool,
+        ) -> Result<LlmResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("generate".to_string()))
+        }
+
+        async fn chat(
+            &self,
+            _model: &str,
+            _messages: &[ChatMessage],
+            _json: bool,
+        ) -> Result<LlmResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("chat".to_string()))
+        }
+
+        async fn chat_with_tools(
+            &self,
+            _model: &str,
+            messages: &[ChatMessage],
+            _tools: &[Tool],
+        ) -> Result<LlmResponse, LlmClientError> {
+            self.requests.lock().unwrap().push(messages.to_vec());
+            self.replies
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| LlmClientError::RequestFailed("no reply scripted".to_string()))
+        }
+
+        async fn chat_structured(
+            &self,
+            _model: &str,
+            _messages: &[ChatMessage],
+            _schema: &Value,
+        ) -> Result<LlmResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("chat_structured".to_string()))
+        }
+
+        async fn embed(
+            &self,
+            _model: &str,
+            _input: &[String],
+            _options: &EmbedOptions,
+        ) -> Result<EmbedResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("embed".to_string()))
+        }
+
+        async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmClientError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn answer(content: &str) -> LlmResponse {
+        LlmResponse {
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn calls(calls: &[(&str, &str, Value)]) -> LlmResponse {
+        LlmResponse {
+            tool_calls: calls
+                .iter()
+                .map(|(id, name, arguments)| ToolCall {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    arguments: arguments.clone(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn agent(client: Arc<MockClient>) -> Agent {
+        let mut agent = Agent::new(client, "mock");
+        agent
+            .register_tool(
+                Tool::new("add", "Adds two numbers", json!({ "type": "object" })),
+                |arguments| async move {
+                    let sum =
+                        arguments["a"].as_i64().unwrap_or(0) + arguments["b"].as_i64().unwrap_or(0);
+                    Ok(sum.to_string())
+                },
+            )
+            .unwrap();
+        agent
+            .register_tool(
+                Tool::new("fail", "Always fails", json!({ "type": "object" })),
+                |_| async { Err("disk full".to_string()) },
+            )
+            .unwrap();
+        agent
+    }
+
+    fn tool_messages(messages: &[ChatMessage]) -> Vec<(String, String)> {
+        messages
+            .iter()
+            .filter(|message| message.role == Role::Tool)
+            .map(|message| (message.tool_call_id.clone().unwrap(), message.text()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn runs_tool_then_returns_final_answer() {
+        let client = MockClient::new(vec![
+            calls(&[("call-1", "add", json!({ "a": 2, "b": 3 }))]),
+            answer("2 + 3 = 5"),
+        ]);
+        let mut agent = agent(client.clone());
+
+        let response = agent.run("What is 2 + 3?").await.unwrap();
+
+        assert_eq!(response.content, "2 + 3 = 5");
+        let requests = client.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            tool_messages(&requests[1]),
+            [("call-1".to_string(), "5".to_string())]
+        );
+        // user, assistant tool call, tool result, final answer
+        assert_eq!(agent.conversation().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn reports_unknown_tool_and_failures_as_tool_results() {
+        let client = MockClient::new(vec![
+            calls(&[
+                ("call-1", "multiply", json!({})),
+                ("call-2", "fail", json!({})),
+            ]),
+            answer("Sorry."),
+        ]);
+        let mut agent = agent(client.clone());
+
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let recorded = steps.clone();
+        agent.on_step(move |step| recorded.lock().unwrap().push(step.clone()));
+
+        agent.run("Multiply").await.unwrap();
+
+        let steps = steps.lock().unwrap();
+        let results = &steps[0].tool_results;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_error));
+        assert_eq!(results[0].output, "Error: Unknown tool `multiply`");
+        assert_eq!(results[1].output, "Error: disk full");
+
+        assert_eq!(
+            tool_messages(&client.requests()[1]),
+            [
+                (
+                    "call-1".to_string(),
+                    "Error: Unknown tool `multiply`".to_string()
+                ),
+                ("call-2".to_string(), "Error: disk full".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_steps() {
+        let client = MockClient::new(
+            (0..5)
+                .map(|i| calls(&[(&format!("call-{i}"), "add", json!({}))]))
+                .collect(),
+        );
+        let mut agent = agent(client.clone()).with_max_steps(3);
+
+        let error = agent.run("Loop forever").await.unwrap_err();
+
+        assert!(matches!(error, LlmClientError::MaxStepsReached(3)));
+        assert_eq!(client.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn calls_hook_once_per_step() {
+        let client = MockClient::new(vec![
+            calls(&[("call-1", "add", json!({ "a": 1, "b": 1 }))]),
+            calls(&[("call-2", "add", json!({ "a": 2, "b": 2 }))]),
+            answer("Done"),
+        ]);
+        let mut agent = agent(client);
+
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let recorded = steps.clone();
+        agent.on_step(move |step| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((step.step, step.tool_results.len()))
+        });
+
+        agent.run("Count").await.unwrap();
+
+        assert_eq!(*steps.lock().unwrap(), [(1, 1), (2, 1), (3, 0)]);
+    }
+}
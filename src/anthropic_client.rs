@@ -0,0 +1,789 @@
+use crate::chat_message::{ContentPart, ImageSource, Role, ToolCall};
+use crate::concurrency_limiter::ConcurrencyLimiter;
+use crate::llm_client::{
+    ChatMessage, EmbedOptions, EmbedResponse, LlmClient, LlmClientError, LlmResponse, ModelInfo,
+    Usage,
+};
+use crate::settings::{EndpointPaths, ServerConfig};
+use crate::structured::schema_name;
+use crate::tool::Tool;
+use crate::web_api_client::{WebApiClient, WebApiClientError, join_url};
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use url::Url;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const MESSAGES_PATH: &str = "v1/messages";
+const MODELS_PATH: &str = "v1/models";
+const DEFAULT_MAX_TOKENS: usize = 4096;
+const JSON_INSTRUCTION: &str = "Respond only with a single valid JSON value.";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: ImageSource,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+    /// Block types this client does not use, such as thinking.
+    #[serde(other)]
+    Unknown,
+}
+
+/// A message in the Anthropic wire format. Only `user` and `assistant` roles
+/// exist; system prompts are sent separately and tool results are user content.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnthropicMessage {
+    pub role: Role,
+    pub content: Vec<ContentBlock>,
+}
+
+/// Splits out the system prompt and merges consecutive messages of the same
+/// role, since the API expects user and assistant turns to alternate.
+pub fn to_anthropic_messages(messages: &[ChatMessage]) -> (Option<String>, Vec<AnthropicMessage>) {
+    let mut system = Vec::new();
+    let mut converted: Vec<AnthropicMessage> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message.role {
+            Role::System => {
+                system.push(message.text());
+                continue;
+            }
+            Role::Tool => (
+                Role::User,
+                vec![ContentBlock::ToolResult {
+                    tool_use_id: message.tool_call_id.clone().unwrap_or_default(),
+                    content: message.text(),
+                }],
+            ),
+            role => {
+                let mut blocks: Vec<ContentBlock> = message
+                    .content
+                    .iter()
+                    .map(|part| match part {
+                        ContentPart::Text { text } => ContentBlock::Text { text: text.clone() },
+                        ContentPart::Image { source } => ContentBlock::Image {
+                            source: source.clone(),
+                        },
+                    })
+                    .collect();
+                blocks.extend(message.tool_calls.iter().map(|call| ContentBlock::ToolUse {
+                    id: call.id.clone(),
+                    name: call.name.clone(),
+                    input: call.arguments.clone(),
+                }));
+                (role, blocks)
+            }
+        };
+
+        if blocks.is_empty() {
+            continue;
+        }
+
+        match converted.last_mut() {
+            Some(last) if last.role == role => last.content.extend(blocks),
+            _ => converted.push(AnthropicMessage {
+                role,
+                content: blocks,
+            }),
+        }
+    }
+
+    let system = if system.is_empty() {
+        None
+    } else {
+        Some(system.join("\n\n"))
+    };
+
+    (system, converted)
+}
+
+fn tool_definition(tool: &Tool) -> Value {
+    json!({
+        "name": tool.name,
+        "description": tool.description,
+        "input_schema": tool.parameters,
+    })
+}
+
+#[derive(Serialize, Debug)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AnthropicUsage {
+    pub input_tokens: Option<usize>,
+    pub output_tokens: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MessagesResponse {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub content: Vec<ContentBlock>,
+    pub stop_reason: Option<String>,
+    pub usage: Option<AnthropicUsage>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentDelta {
+    TextDelta {
+        text: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MessageDelta {
+    pub stop_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    MessageStart {
+        message: MessagesResponse,
+    },
+    ContentBlockStart {
+        index: usize,
+        content_block: ContentBlock,
+    },
+    ContentBlockDelta {
+        index: usize,
+        delta: ContentDelta,
+    },
+    ContentBlockStop {
+        index: usize,
+    },
+    MessageDelta {
+        delta: MessageDelta,
+        usage: Option<AnthropicUsage>,
+    },
+    MessageStop,
+    Ping,
+    Error {
+        error: Value,
+    },
+}
+
+/// Rebuilds the full response from streamed events, including tool calls
+/// whose input arrives as partial JSON.
+#[derive(Debug, Default, Clone)]
+pub struct MessageAccumulator {
+    model: String,
+    blocks: Vec<ContentBlock>,
+    partial_json: Vec<String>,
+    stop_reason: Option<String>,
+    usage: AnthropicUsage,
+}
+
+impl MessageAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: &StreamEvent) {
+        match event {
+            StreamEvent::MessageStart { message } => {
+                self.model = message.model.clone();
+                if let Some(usage) = &message.usage {
+                    self.usage.input_tokens = usage.input_tokens;
+                }
+            }
+            StreamEvent::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                if self.blocks.len() <= *index {
+                    self.blocks.resize(*index + 1, ContentBlock::Unknown);
+                    self.partial_json.resize(*index + 1, String::new());
+                }
+                self.blocks[*index] = content_block.clone();
+            }
+            StreamEvent::ContentBlockDelta { index, delta } => match delta {
+                ContentDelta::TextDelta { text } => {
+                    if let Some(ContentBlock::Text { text: existing }) = self.blocks.get_mut(*index)
+                    {
+                        existing.push_str(text);
+                    }
+                }
+                ContentDelta::InputJsonDelta { partial_json } => {
+                    if let Some(buffer) = self.partial_json.get_mut(*index) {
+                        buffer.push_str(partial_json);
+                    }
+                }
+                ContentDelta::Unknown => {}
+            },
+            StreamEvent::ContentBlockStop { index } => {
+                if let Some(ContentBlock::ToolUse { input, .. }) = self.blocks.get_mut(*index)
+                    && !self.partial_json[*index].is_empty()
+                {
+                    *input = serde_json::from_str(&self.partial_json[*index])
+                        .unwrap_or(Value::String(self.partial_json[*index].clone()));
+                }
+            }
+            StreamEvent::MessageDelta { delta, usage } => {
+                if delta.stop_reason.is_some() {
+                    self.stop_reason = delta.stop_reason.clone();
+                }
+                if let Some(usage) = usage {
+                    self.usage.output_tokens = usage.output_tokens;
+                }
+            }
+            StreamEvent::MessageStop | StreamEvent::Ping | StreamEvent::Error { .. } => {}
+        }
+    }
+
+    pub fn content(&self) -> String {
+        text_content(&self.blocks)
+    }
+
+    pub fn stop_reason(&self) -> Option<&str> {
+        self.stop_reason.as_deref()
+    }
+
+    pub fn response(&self) -> LlmResponse {
+        MessagesResponse {
+            id: String::new(),
+            model: self.model.clone(),
+            content: self.blocks.clone(),
+            stop_reason: self.stop_reason.clone(),
+            usage: Some(self.usage.clone()),
+        }
+        .into()
+    }
+}
+
+fn text_content(blocks: &[ContentBlock]) -> String {
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsResponse {
+    data: Vec<ModelsData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsData {
+    id: String,
+}
+
+pub struct AnthropicClient {
+    auth_api_client: WebApiClient,
+    base_url: Url,
+    paths: EndpointPaths,
+    max_tokens: usize,
+}
+
+impl AnthropicClient {
+    /// Clients given the same `concurrency_limiter` share its limit.
+    pub fn new(
+        setting: &ServerConfig,
+        api_key: Option<String>,
+        concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
+    ) -> Result<Self, WebApiClientError> {
+        let api_key = match api_key {
+            Some(api_key) if !api_key.is_empty() => api_key,
+            _ => {
+                return Err(WebApiClientError::InvalidApiKey(
+                    "API key cannot be empty".to_string(),
+                ));
+            }
+        };
+
+        let mut auth_api_client = WebApiClient::for_server(setting, concurrency_limiter);
+
+        if let Err(e) = auth_api_client.add_header("x-api-key", api_key) {
+            return Err(WebApiClientError::InvalidApiKey(format!(
+                "Failed to add header to WebApiClient: {e}"
+            )));
+        }
+        auth_api_client.add_header("anthropic-version", ANTHROPIC_VERSION.to_string())?;
+
+        let base_url = match Url::parse(&setting.base_api_url) {
+            Ok(url) => url,
+            Err(e) => {
+                return Err(WebApiClientError::InvalidInput(format!(
+                    "Failed to parse base API URL ({}): {}",
+                    setting.base_api_url, e
+                )));
+            }
+        };
+
+        Ok(Self {
+            auth_api_client,
+            base_url,
+            paths: setting.paths.clone(),
+            max_tokens: setting.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        })
+    }
+
+    fn messages_request(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+        json: bool,
+        stream: bool,
+    ) -> MessagesRequest {
+        let (mut system, messages) = to_anthropic_messages(messages);
+
+        // there is no JSON mode, so ask for it in the system prompt
+        if json {
+            system = Some(match system {
+                Some(system) => format!("{system}\n\n{JSON_INSTRUCTION}"),
+                None => JSON_INSTRUCTION.to_string(),
+            });
+        }
+
+        MessagesRequest {
+            model: model.to_string(),
+            max_tokens: self.max_tokens,
+            system,
+            messages,
+            tools: tools.iter().map(tool_definition).collect(),
+            tool_choice: None,
+            stream,
+        }
+    }
+
+    pub async fn messages(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+        json: bool,
+    ) -> Result<MessagesResponse, WebApiClientError> {
+        let request = self.messages_request(model, messages, tools, json, false);
+        self.send_messages(request).await
+    }
+
+    /// Forces a call to a tool whose input schema is `schema`, which is how
+    /// the Messages API produces schema-conforming output. The tool input is
+    /// returned as the text of the response.
+    pub async fn messages_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<MessagesResponse, WebApiClientError> {
+        let name = schema_name(schema);
+        let tool = Tool::new(
+            &name,
+            "Respond with data matching this schema",
+            schema.clone(),
+        );
+
+        let mut request = self.messages_request(model, messages, &[tool], false, false);
+        request.tool_choice = Some(json!({"type": "tool", "name": name}));
+
+        let mut response = self.send_messages(request).await?;
+        response.content = response
+            .content
+            .into_iter()
+            .map(|block| match block {
+                ContentBlock::ToolUse { input, .. } => ContentBlock::Text {
+                    text: input.to_string(),
+                },
+                other => other,
+            })
+            .collect();
+
+        Ok(response)
+    }
+
+    async fn send_messages(
+        &self,
+        request: MessagesRequest,
+    ) -> Result<MessagesResponse, WebApiClientError> {
+        let url = join_url(
+            &self.base_url,
+            self.paths.chat.as_deref().unwrap_or(MESSAGES_PATH),
+        )?;
+
+        let json_value = self
+            .auth_api_client
+            .post_request(url, &json!(request))
+            .await?;
+
+        match serde_json::from_value(json_value) {
+            Ok(response) => Ok(response),
+            Err(e) => Err(WebApiClientError::ParseError(format!(
+                "Failed to parse messages response: {e}"
+            ))),
+        }
+    }
+
+    /// Streams the response as Messages API events. Feed them to a
+    /// `MessageAccumulator` to get the complete response.
+    pub async fn messages_stream(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<BoxStream<'static, Result<StreamEvent, WebApiClientError>>, WebApiClientError> {
+        let url = join_url(
+            &self.base_url,
+            self.paths.chat.as_deref().unwrap_or(MESSAGES_PATH),
+        )?;
+
+        let request = self.messages_request(model, messages, tools, false, true);
+        let events = self
+            .auth_api_client
+            .post_event_stream(url, &json!(request))
+            .await?;
+
+        Ok(events
+            .map(|event| {
+                let event = event?;
+                match serde_json::from_str(&event.data) {
+                    Ok(StreamEvent::Error { error }) => Err(WebApiClientError::PostFailed(
+                        format!("Anthropic stream error: {error}"),
+                    )),
+                    Ok(event) => Ok(event),
+                    Err(e) => Err(WebApiClientError::ParseError(format!(
+                        "Failed to parse messages stream event: {e}"
+                    ))),
+                }
+            })
+            .boxed())
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, WebApiClientError> {
+        let url = join_url(
+            &self.base_url,
+            self.paths.models.as_deref().unwrap_or(MODELS_PATH),
+        )?;
+
+        let json_value = self.auth_api_client.get_request(url).await?;
+
+        let parsed: ModelsResponse = match serde_json::from_value(json_value) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return Err(WebApiClientError::ParseError(format!(
+                    "Failed to parse models response: {e}"
+                )));
+            }
+        };
+
+        Ok(parsed
+            .data
+            .into_iter()
+            .map(|model| ModelInfo {
+                name: model.id,
+                owned_by: Some("anthropic".to_string()),
+                size: None,
+            })
+            .collect())
+    }
+}
+
+impl From<MessagesResponse> for LlmResponse {
+    fn from(response: MessagesResponse) -> Self {
+        let usage = response.usage.unwrap_or_default();
+
+        LlmResponse {
+            model: response.model,
+            content: text_content(&response.content),
+            finish_reason: response.stop_reason,
+            usage: Some(Usage::new(usage.input_tokens, usage.output_tokens)),
+            tool_calls: response
+                .content
+                .into_iter()
+                .filter_map(|block| match block {
+                    ContentBlock::ToolUse { id, name, input } => Some(ToolCall {
+                        id,
+                        name,
+                        arguments: input,
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmClient for AnthropicClient {
+    async fn generate(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let messages = [
+            ChatMessage::system(system_prompt),
+            ChatMessage::user(prompt),
+        ];
+
+        self.chat(model, &messages, json).await
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = self.messages(model, messages, &[], json).await?;
+        Ok(response.into())
+    }
+
+    async fn chat_with_tools(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = self.messages(model, messages, tools, false).await?;
+        Ok(response.into())
+    }
+
+    async fn chat_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = self.messages_structured(model, messages, schema).await?;
+        Ok(response.into())
+    }
+
+    async fn embed(
+        &self,
+        _model: &str,
+        _input: &[String],
+        _options: &EmbedOptions,
+    ) -> Result<EmbedResponse, LlmClientError> {
+        Err(LlmClientError::Unsupported(
+            "Anthropic does not provide an embeddings API".to_string(),
+        ))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmClientError> {
+        Ok(AnthropicClient::list_models(self).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse;
+    use bytes::Bytes;
+    use futures::stream;
+
+    // a tool call streamed by the Messages API, with its input split mid-token
+    const TOOL_USE_STREAM: &str = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-sonnet-4-5","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":472,"output_tokens":2}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}
+
+event: ping
+data: {"type": "ping"}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me check"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":" the weather."}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: content_block_start
+data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_01","name":"get_weather","input":{}}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":""}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"location\": \"San Fra"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"ncisco, CA\", \"unit\": \"celsius\"}"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":1}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":89}}
+
+event: message_stop
+data: {"type":"message_stop"}
+
+"#;
+
+    async fn accumulate(body: &'static str) -> MessageAccumulator {
+        let chunks: Vec<Result<Bytes, WebApiClientError>> = body
+            .as_bytes()
+            .chunks(7)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+
+        let mut events = sse::events(stream::iter(chunks).boxed());
+        let mut accumulator = MessageAccumulator::new();
+        while let Some(event) = events.next().await {
+            let event: StreamEvent = serde_json::from_str(&event.unwrap().data).unwrap();
+            accumulator.push(&event);
+        }
+        accumulator
+    }
+
+    fn messages_json(messages: &[ChatMessage]) -> (Option<String>, Value) {
+        let (system, converted) = to_anthropic_messages(messages);
+        (system, serde_json::to_value(converted).unwrap())
+    }
+
+    #[test]
+    fn merges_system_prompts() {
+        let (system, messages) = messages_json(&[
+            ChatMessage::system("Be brief."),
+            ChatMessage::user("Hi"),
+            ChatMessage::system("Answer in French."),
+        ]);
+
+        assert_eq!(system.as_deref(), Some("Be brief.\n\nAnswer in French."));
+        assert_eq!(
+            messages,
+            json!([{ "role": "user", "content": [{ "type": "text", "text": "Hi" }] }])
+        );
+
+        let (system, _) = to_anthropic_messages(&[ChatMessage::user("Hi")]);
+        assert_eq!(system, None);
+    }
+
+    #[test]
+    fn merges_consecutive_messages_of_one_role() {
+        let (_, messages) = messages_json(&[
+            ChatMessage::user("One"),
+            ChatMessage::user("Two").with_image_base64("image/png", "iVBORw0KGgo="),
+            ChatMessage::assistant("Three"),
+        ]);
+
+        assert_eq!(
+            messages,
+            json!([
+                { "role": "user", "content": [
+                    { "type": "text", "text": "One" },
+                    { "type": "text", "text": "Two" },
+                    { "type": "image", "source": {
+                        "type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="
+                    } },
+                ] },
+                { "role": "assistant", "content": [{ "type": "text", "text": "Three" }] },
+            ])
+        );
+    }
+
+    #[test]
+    fn converts_tool_calls_and_results() {
+        let (_, messages) = messages_json(&[
+            ChatMessage::user("Weather in Oslo?"),
+            ChatMessage::assistant("Checking.").with_tool_calls(vec![ToolCall {
+                id: "toolu_01".to_string(),
+                name: "get_weather".to_string(),
+                arguments: json!({ "city": "Oslo" }),
+            }]),
+            ChatMessage::tool("toolu_01", "rain"),
+            ChatMessage::user("Thanks"),
+        ]);
+
+        // the tool result and the next user message form one user turn
+        assert_eq!(
+            messages,
+            json!([
+                { "role": "user", "content": [{ "type": "text", "text": "Weather in Oslo?" }] },
+                { "role": "assistant", "content": [
+                    { "type": "text", "text": "Checking." },
+                    { "type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": { "city": "Oslo" } },
+                ] },
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "toolu_01", "content": "rain" },
+                    { "type": "text", "text": "Thanks" },
+                ] },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn accumulates_streamed_tool_call() {
+        let accumulator = accumulate(TOOL_USE_STREAM).await;
+
+        assert_eq!(accumulator.content(), "Let me check the weather.");
+        assert_eq!(accumulator.stop_reason(), Some("tool_use"));
+
+        let response = accumulator.response();
+        assert_eq!(response.model, "claude-sonnet-4-5");
+        assert_eq!(response.content, "Let me check the weather.");
+        assert_eq!(response.usage, Some(Usage::new(Some(472), Some(89))));
+        assert_eq!(
+            response.tool_calls,
+            [ToolCall {
+                id: "toolu_01".to_string(),
+                name: "get_weather".to_string(),
+                arguments: json!({ "location": "San Francisco, CA", "unit": "celsius" }),
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_tool_input_that_is_not_json() {
+        let mut accumulator = MessageAccumulator::new();
+        for event in [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_01","name":"echo","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{\"text\": "}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+        ] {
+            accumulator.push(&serde_json::from_str(event).unwrap());
+        }
+
+        assert_eq!(
+            accumulator.response().tool_calls[0].arguments,
+            json!("{\"text\": ")
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Display;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = String;
+
+    fn try_from(role: &str) -> Result<Self, Self::Error> {
+        match role.to_lowercase().as_str() {
+            "system" | "developer" => Ok(Role::System),
+            "user" => Ok(Role::User),
+            "assistant" | "model" => Ok(Role::Assistant),
+            "tool" | "function" => Ok(Role::Tool),
+            other => Err(format!("Unknown message role `{other}`")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    Url { url: String },
+    Base64 { media_type: String, data: String },
+}
+
+impl ImageSource {
+    /// The image as a URL, using a `data:` URL for inline images.
+    pub fn to_url(&self) -> String {
+        match self {
+            ImageSource::Url { url } => url.clone(),
+            ImageSource::Base64 { media_type, data } => format!("data:{media_type};base64,{data}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    Image { source: ImageSource },
+}
+
+/// A tool invocation requested by the model. `arguments` is the parsed JSON
+/// object, whichever way the provider encoded it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    pub fn parse_arguments<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(&self.arguments)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    #[serde(default)]
+    pub content: Vec<ContentPart>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, text: &str) -> Self {
+        let content = if text.is_empty() {
+            Vec::new()
+        } else {
+            vec![ContentPart::Text {
+                text: text.to_string(),
+            }]
+        };
+
+        Self {
+            role,
+            content,
+            name: None,
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+
+    pub fn system(text: &str) -> Self {
+        Self::new(Role::System, text)
+    }
+
+    pub fn user(text: &str) -> Self {
+        Self::new(Role::User, text)
+    }
+
+    pub fn assistant(text: &str) -> Self {
+        Self::new(Role::Assistant, text)
+    }
+
+    /// The result of running the tool call identified by `tool_call_id`.
+    pub fn tool(tool_call_id: &str, text: &str) -> Self {
+        let mut message = Self::new(Role::Tool, text);
+        message.tool_call_id = Some(tool_call_id.to_string());
+        message
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.content.push(ContentPart::Text {
+            text: text.to_string(),
+        });
+        self
+    }
+
+    pub fn with_image_url(mut self, url: &str) -> Self {
+        self.content.push(ContentPart::Image {
+            source: ImageSource::Url {
+                url: url.to_string(),
+            },
+        });
+        self
+    }
+
+    pub fn with_image_base64(mut self, media_type: &str, data: &str) -> Self {
+        self.content.push(ContentPart::Image {
+            source: ImageSource::Base64 {
+                media_type: media_type.to_string(),
+                data: data.to_string(),
+            },
+        });
+        self
+    }
+
+    pub fn with_tool_calls(mut self, tool_calls: Vec<ToolCall>) -> Self {
+        self.tool_calls = tool_calls;
+        self
+    }
+
+    /// All text parts joined together, ignoring images.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                ContentPart::Image { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &ImageSource> {
+        self.content.iter().filter_map(|part| match part {
+            ContentPart::Image { source } => Some(source),
+            ContentPart::Text { .. } => None,
+        })
+    }
+}
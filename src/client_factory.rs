@@ -0,0 +1,91 @@
+use crate::anthropic_client::AnthropicClient;
+use crate::concurrency_limiter::ConcurrencyLimiter;
+use crate::gemini_client::GeminiClient;
+use crate::llm_client::{LlmClient, LlmClientError};
+use crate::ollama_client::OllamaClient;
+use crate::openai_client::OpenAiClient;
+use crate::secrets::Secrets;
+use crate::settings::{ServerConfig, Settings};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Builds one client per configured server up front, so unknown `api_type`
+/// values and missing secrets are reported before any request is made.
+/// Servers with the same name share one concurrency limit.
+pub struct ClientFactory {
+    clients: HashMap<String, Arc<dyn LlmClient>>,
+    concurrency_limiters: HashMap<String, Arc<ConcurrencyLimiter>>,
+}
+
+impl ClientFactory {
+    pub fn new(settings: &Settings, secrets: &Secrets) -> Result<Self, LlmClientError> {
+        let mut clients: HashMap<String, Arc<dyn LlmClient>> = HashMap::new();
+        let mut concurrency_limiters = HashMap::new();
+
+        for server in &settings.servers {
+            let api_key = match &server.secret {
+                Some(name) => match secrets.get_by_name(name) {
+                    Ok(secret) => Some(secret.value),
+                    Err(e) => {
+                        return Err(LlmClientError::InvalidApiKey(format!(
+                            "Server {} references a missing secret: {e}",
+                            server.name
+                        )));
+                    }
+                },
+                None => None,
+            };
+
+            if !concurrency_limiters.contains_key(&server.name)
+                && let Some(limiter) = ConcurrencyLimiter::for_server(server)
+            {
+                concurrency_limiters.insert(server.name.clone(), limiter);
+            }
+            let concurrency_limiter = concurrency_limiters.get(&server.name).cloned();
+
+            let client = build_client(server, api_key, concurrency_limiter)?;
+            clients.insert(server.name.clone(), client);
+        }
+
+        Ok(Self {
+            clients,
+            concurrency_limiters,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn LlmClient>, LlmClientError> {
+        match self.clients.get(name) {
+            Some(client) => Ok(client.clone()),
+            None => Err(LlmClientError::InvalidInput(format!(
+                "Server {name} not found"
+            ))),
+        }
+    }
+
+    /// The concurrency limit of server `name`, for reading its in-flight and
+    /// queued request counts.
+    pub fn concurrency_limiter(&self, name: &str) -> Option<Arc<ConcurrencyLimiter>> {
+        self.concurrency_limiters.get(name).cloned()
+    }
+}
+
+fn build_client(
+    server: &ServerConfig,
+    api_key: Option<String>,
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+) -> Result<Arc<dyn LlmClient>, LlmClientError> {
+    match server.api_type.to_lowercase().as_str() {
+        "anthropic" => Ok(Arc::new(AnthropicClient::new(server, api_key, limiter)?)),
+        "gemini" => Ok(Arc::new(GeminiClient::new(server, api_key, limiter)?)),
+        "ollama" => Ok(Arc::new(OllamaClient::new(server, api_key, limiter)?)),
+        "openai" | "azure" => Ok(Arc::new(OpenAiClient::new(
+            server,
+            api_key.as_ref(),
+            limiter,
+        )?)),
+        other => Err(LlmClientError::Unsupported(format!(
+            "Server {} has unknown api_type `{other}`",
+            server.name
+        ))),
+    }
+}
@@ -0,0 +1,195 @@
+use crate::settings::ServerConfig;
+use crate::web_api_client::WebApiClientError;
+use log::debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Caps the number of requests in flight to one server. Requests over the cap
+/// wait in a queue, optionally bounded in length and waiting time.
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    semaphore: Arc<Semaphore>,
+    max_concurrent_requests: usize,
+    max_queued_requests: Option<usize>,
+    queue_timeout: Option<Duration>,
+    queued: AtomicUsize,
+}
+
+/// A slot held for as long as a request is in flight.
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(
+        max_concurrent_requests: usize,
+        max_queued_requests: Option<usize>,
+        queue_timeout: Option<Duration>,
+    ) -> Self {
+        let max_concurrent_requests = max_concurrent_requests.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrent_requests)),
+            max_concurrent_requests,
+            max_queued_requests,
+            queue_timeout,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// A limiter for `setting`, or `None` when it sets no
+    /// `max_concurrent_requests`.
+    pub fn for_server(setting: &ServerConfig) -> Option<Arc<Self>> {
+        let max_concurrent_requests = setting.max_concurrent_requests?;
+
+        Some(Arc::new(Self::new(
+            max_concurrent_requests,
+            setting.max_queued_requests,
+            setting.queue_timeout.map(Duration::from_secs),
+        )))
+    }
+
+    pub fn max_concurrent_requests(&self) -> usize {
+        self.max_concurrent_requests
+    }
+
+    /// Requests currently holding a slot.
+    pub fn in_flight(&self) -> usize {
+        self.max_concurrent_requests - self.semaphore.available_permits()
+    }
+
+    /// Requests currently waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Waits for a free slot. Fails with `Overloaded` when the queue is full
+    /// or the wait exceeds the queue timeout.
+    pub async fn acquire(&self) -> Result<ConcurrencyPermit, WebApiClientError> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Ok(ConcurrencyPermit { _permit: permit });
+        }
+
+        let position = self.queued.fetch_add(1, Ordering::SeqCst);
+        let _guard = QueuedGuard(&self.queued);
+
+        if let Some(max_queued_requests) = self.max_queued_requests
+            && position >= max_queued_requests
+        {
+            return Err(WebApiClientError::Overloaded(format!(
+                "Request queue is full ({max_queued_requests} waiting)"
+            )));
+        }
+
+        debug!(
+            "All {} request slots in use, waiting in queue",
+            self.max_concurrent_requests
+        );
+
+        let acquire = self.semaphore.clone().acquire_owned();
+        let permit = match self.queue_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, acquire).await {
+                Ok(permit) => permit,
+                Err(_) => {
+                    return Err(WebApiClientError::Overloaded(format!(
+                        "No request slot free after {timeout:?}"
+                    )));
+                }
+            },
+            None => acquire.await,
+        };
+
+        match permit {
+            Ok(permit) => Ok(ConcurrencyPermit { _permit: permit }),
+            Err(e) => Err(WebApiClientError::Overloaded(format!(
+                "Request slots unavailable: {e}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_in_flight_and_frees_slot_on_drop() {
+        let limiter = ConcurrencyLimiter::new(2, None, None);
+        assert_eq!(limiter.in_flight(), 0);
+
+        let first = limiter.acquire().await.unwrap();
+        let second = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.in_flight(), 2);
+
+        drop(first);
+        assert_eq!(limiter.in_flight(), 1);
+
+        let _third = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.in_flight(), 2);
+        drop(second);
+        assert_eq!(limiter.in_flight(), 1);
+    }
+
+    #[tokio::test]
+    async fn queued_request_gets_slot_when_released() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, None, None));
+        let permit = limiter.acquire().await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.map(|_| ()) }
+        });
+        while limiter.queued() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(limiter.queued(), 1);
+
+        drop(permit);
+        waiting.await.unwrap().unwrap();
+        assert_eq!(limiter.queued(), 0);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_when_queue_is_full() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, Some(1), None));
+        let _permit = limiter.acquire().await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.map(|_| ()) }
+        });
+        while limiter.queued() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let rejected = limiter.acquire().await;
+        assert!(matches!(rejected, Err(WebApiClientError::Overloaded(_))));
+        assert_eq!(limiter.queued(), 1);
+
+        waiting.abort();
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_queue_timeout() {
+        let limiter = ConcurrencyLimiter::new(1, None, Some(Duration::from_millis(50)));
+        let _permit = limiter.acquire().await.unwrap();
+
+        let start = std::time::Instant::now();
+        let timed_out = limiter.acquire().await;
+        assert!(matches!(timed_out, Err(WebApiClientError::Overloaded(_))));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(limiter.queued(), 0);
+        assert_eq!(limiter.in_flight(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::llm_client::{ChatMessage, LlmClient, LlmClientError, LlmResponse};
+use crate::tool::Tool;
+use serde::{Deserialize, Serialize};
+
+/// An ordered message history that can be sent to any `LlmClient`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    messages: Vec<ChatMessage>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_system(system_prompt: &str) -> Self {
+        let mut conversation = Self::new();
+        conversation.push_system(system_prompt);
+        conversation
+    }
+
+    pub fn push(&mut self, message: ChatMessage) {
+        self.messages.push(message);
+    }
+
+    pub fn push_system(&mut self, content: &str) {
+        self.push(ChatMessage::system(content));
+    }
+
+    pub fn push_user(&mut self, content: &str) {
+        self.push(ChatMessage::user(content));
+    }
+
+    pub fn push_assistant(&mut self, content: &str) {
+        self.push(ChatMessage::assistant(content));
+    }
+
+    pub fn push_tool(&mut self, tool_call_id: &str, content: &str) {
+        self.push(ChatMessage::tool(tool_call_id, content));
+    }
+
+    pub fn messages(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    pub fn last(&self) -> Option<&ChatMessage> {
+        self.messages.last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+
+    /// Sends the history and appends the assistant reply to it. On error the
+    /// history is left as it was.
+    pub async fn send(
+        &mut self,
+        client: &dyn LlmClient,
+        model: &str,
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = client.chat(model, &self.messages, json).await?;
+        self.push(response.message());
+        Ok(response)
+    }
+
+    /// Sends the history with `tools` available. Any tool calls are kept on
+    /// the appended assistant message; answer them with `push_tool`.
+    pub async fn send_with_tools(
+        &mut self,
+        client: &dyn LlmClient,
+        model: &str,
+        tools: &[Tool],
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = client.chat_with_tools(model, &self.messages, tools).await?;
+        self.push(response.message());
+        Ok(response)
+    }
+
+    pub async fn send_user(
+        &mut self,
+        client: &dyn LlmClient,
+        model: &str,
+        content: &str,
+    ) -> Result<LlmResponse, LlmClientError> {
+        self.push_user(content);
+        self.send(client, model, false).await
+    }
+}
@@ -0,0 +1,187 @@
+use crate::client_factory::ClientFactory;
+use crate::llm_client::{LlmClientError, Usage};
+use crate::prompt_template::{PromptRenderer, TemplateMode};
+use crate::rag::{RagResponse, context_variables, retrieve};
+use crate::secrets::Secrets;
+use crate::settings::Settings;
+use crate::vector_store::VectorStore;
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+pub struct EndpointResponse {
+    pub server: String,
+    pub model: String,
+    pub content: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+}
+
+impl EndpointResponse {
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, LlmClientError> {
+        serde_json::from_str(&self.content).map_err(|e| {
+            LlmClientError::ParseError(format!("Failed to parse endpoint response: {e}"))
+        })
+    }
+}
+
+/// Runs the endpoints declared in `Settings` against the servers they name.
+pub struct EndpointRunner {
+    settings: Settings,
+    factory: ClientFactory,
+    renderer: PromptRenderer,
+    stores: Mutex<HashMap<String, Arc<VectorStore>>>,
+}
+
+impl EndpointRunner {
+    pub fn new(settings: Settings, factory: ClientFactory) -> Result<Self, LlmClientError> {
+        let mut renderer = PromptRenderer::new();
+        if let Some(dir) = &settings.prompts_dir {
+            renderer.load_partials(Path::new(dir))?;
+        }
+
+        Ok(Self {
+            settings,
+            factory,
+            renderer,
+            stores: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // stores are loaded on first use, and RAG endpoints sharing a store file
+    // share one loaded copy
+    fn store(&self, path: &str) -> Result<Arc<VectorStore>, LlmClientError> {
+        let mut stores = self.stores.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(store) = stores.get(path) {
+            return Ok(store.clone());
+        }
+
+        if !Path::new(path).is_file() {
+            return Err(LlmClientError::InvalidInput(format!(
+                "Vector store {path} does not exist"
+            )));
+        }
+
+        let store = Arc::new(VectorStore::load_jsonl(Path::new(path))?);
+        stores.insert(path.to_string(), store.clone());
+        Ok(store)
+    }
+
+    pub fn from_settings(settings: Settings, secrets: &Secrets) -> Result<Self, LlmClientError> {
+        let factory = ClientFactory::new(&settings, secrets)?;
+        Self::new(settings, factory)
+    }
+
+    pub async fn run(
+        &self,
+        path: &str,
+        inputs: &HashMap<String, Value>,
+    ) -> Result<EndpointResponse, LlmClientError> {
+        let endpoint = self
+            .settings
+            .get_endpoint_by_path(path)
+            .map_err(|e| LlmClientError::InvalidInput(e.to_string()))?;
+
+        let server = self
+            .settings
+            .get_server_config_by_name(&endpoint.server)
+            .map_err(|e| LlmClientError::InvalidInput(e.to_string()))?;
+
+        let mode = TemplateMode::try_from(endpoint.template.as_str())?;
+        let system_prompt = self
+            .renderer
+            .render(&endpoint.system_prompt, inputs, mode)?;
+        let user_prompt = self.renderer.render(&endpoint.user_prompt, inputs, mode)?;
+
+        debug!("Running endpoint {path} on server {}", server.name);
+
+        let client = self.factory.get(&server.name)?;
+        let response = client
+            .generate(&server.model, &system_prompt, &user_prompt, endpoint.json)
+            .await?;
+
+        Ok(EndpointResponse {
+            server: server.name,
+            model: response.model,
+            content: response.content,
+            finish_reason: response.finish_reason,
+            usage: response.usage,
+        })
+    }
+
+    /// Answers `query` from the endpoint's vector store. `inputs` are passed to
+    /// the prompts alongside the retrieved context.
+    pub async fn run_rag(
+        &self,
+        path: &str,
+        query: &str,
+        inputs: &HashMap<String, Value>,
+    ) -> Result<RagResponse, LlmClientError> {
+        let endpoint = self
+            .settings
+            .get_rag_endpoint_by_path(path)
+            .map_err(|e| LlmClientError::InvalidInput(e.to_string()))?;
+
+        let server = self
+            .settings
+            .get_server_config_by_name(&endpoint.server)
+            .map_err(|e| LlmClientError::InvalidInput(e.to_string()))?;
+
+        let embedding_server = match &endpoint.embedding_server {
+            Some(name) => self
+                .settings
+                .get_server_config_by_name(name)
+                .map_err(|e| LlmClientError::InvalidInput(e.to_string()))?,
+            None => server.clone(),
+        };
+        let store = self.store(&endpoint.store)?;
+
+        debug!(
+            "Retrieving {} chunks for RAG endpoint {path} from {}",
+            endpoint.top_k, endpoint.store
+        );
+
+        let embedder = self.factory.get(&embedding_server.name)?;
+        let sources = retrieve(
+            embedder.as_ref(),
+            &endpoint.embedding_model,
+            &store,
+            query,
+            endpoint.top_k,
+            endpoint.similarity,
+            None,
+        )
+        .await?;
+
+        let mut variables = inputs.clone();
+        variables.extend(context_variables(query, &sources, &endpoint.text_key));
+
+        let mode = TemplateMode::try_from(endpoint.template.as_str())?;
+        let system_prompt = self
+            .renderer
+            .render(&endpoint.system_prompt, &variables, mode)?;
+        let user_prompt = self
+            .renderer
+            .render(&endpoint.user_prompt, &variables, mode)?;
+
+        let client = self.factory.get(&server.name)?;
+        let response = client
+            .generate(&server.model, &system_prompt, &user_prompt, endpoint.json)
+            .await?;
+
+        Ok(RagResponse {
+            answer: EndpointResponse {
+                server: server.name,
+                model: response.model,
+                content: response.content,
+                finish_reason: response.finish_reason,
+                usage: response.usage,
+            },
+            sources,
+        })
+    }
+}
@@ -0,0 +1,83 @@
+use crate::llm_client::LlmClientError;
+use crate::openai_client::OpenAiClientError;
+use crate::prompt_template::PromptTemplateError;
+use crate::vector_store::VectorStoreError;
+use crate::web_api_client::WebApiClientError;
+use std::fmt::Display;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Any error this crate returns, wrapping the error of the module it came from.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or parsing settings and secrets.
+    Config(std::io::Error),
+    Http(WebApiClientError),
+    Client(LlmClientError),
+    OpenAi(OpenAiClientError),
+    Template(PromptTemplateError),
+    VectorStore(VectorStoreError),
+}
+
+// the wrapped error carries the details and is reported as the source
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Config(_) => write!(f, "Invalid configuration"),
+            Error::Http(_) => write!(f, "HTTP request failed"),
+            Error::Client(_) => write!(f, "LLM client error"),
+            Error::OpenAi(_) => write!(f, "OpenAI client error"),
+            Error::Template(_) => write!(f, "Prompt template error"),
+            Error::VectorStore(_) => write!(f, "Vector store error"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(error) => Some(error),
+            Error::Http(error) => Some(error),
+            Error::Client(error) => Some(error),
+            Error::OpenAi(error) => Some(error),
+            Error::Template(error) => Some(error),
+            Error::VectorStore(error) => Some(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Config(error)
+    }
+}
+
+impl From<WebApiClientError> for Error {
+    fn from(error: WebApiClientError) -> Self {
+        Error::Http(error)
+    }
+}
+
+impl From<LlmClientError> for Error {
+    fn from(error: LlmClientError) -> Self {
+        Error::Client(error)
+    }
+}
+
+impl From<OpenAiClientError> for Error {
+    fn from(error: OpenAiClientError) -> Self {
+        Error::OpenAi(error)
+    }
+}
+
+impl From<PromptTemplateError> for Error {
+    fn from(error: PromptTemplateError) -> Self {
+        Error::Template(error)
+    }
+}
+
+impl From<VectorStoreError> for Error {
+    fn from(error: VectorStoreError) -> Self {
+        Error::VectorStore(error)
+    }
+}
@@ -0,0 +1,817 @@
+use crate::chat_message::{ImageSource, Role, ToolCall};
+use crate::concurrency_limiter::ConcurrencyLimiter;
+use crate::llm_client::{
+    ChatMessage, EmbedOptions, EmbedResponse, LlmClient, LlmClientError, LlmResponse, ModelInfo,
+    Usage,
+};
+use crate::settings::{SafetySetting, ServerConfig};
+use crate::tool::Tool;
+use crate::web_api_client::{WebApiClient, WebApiClientError, join_url};
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use url::Url;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiInlineData {
+    pub mime_type: String,
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeminiFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeminiFunctionResponse {
+    pub name: String,
+    pub response: Value,
+}
+
+/// One part of a Gemini message. Exactly one of the fields is set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPart {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inline_data: Option<GeminiInlineData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<GeminiFunctionCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_response: Option<GeminiFunctionResponse>,
+    /// Set on the model's reasoning summaries, which are not part of the answer.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub thought: bool,
+}
+
+impl GeminiPart {
+    fn text(text: &str) -> Self {
+        Self {
+            text: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A message in the Gemini wire format, with role `user` or `model`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeminiContent {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub role: String,
+    #[serde(default)]
+    pub parts: Vec<GeminiPart>,
+}
+
+/// Splits out the system instruction and converts the rest of the messages.
+/// Tool results are sent back as `functionResponse` parts of a user message.
+pub fn to_gemini_contents(
+    messages: &[ChatMessage],
+) -> Result<(Option<GeminiContent>, Vec<GeminiContent>), WebApiClientError> {
+    let mut system = Vec::new();
+    let mut contents: Vec<GeminiContent> = Vec::new();
+
+    for (i, message) in messages.iter().enumerate() {
+        let (role, parts) = match message.role {
+            Role::System => {
+                system.push(GeminiPart::text(&message.text()));
+                continue;
+            }
+            Role::Tool => {
+                let name = match &message.name {
+                    Some(name) => name.clone(),
+                    None => tool_call_name(&messages[..i], message.tool_call_id.as_deref())?,
+                };
+
+                // the response must be an object
+                let text = message.text();
+                let response = match serde_json::from_str::<Value>(&text) {
+                    Ok(value) if value.is_object() => value,
+                    _ => json!({ "content": text }),
+                };
+
+                (
+                    "user",
+                    vec![GeminiPart {
+                        function_response: Some(GeminiFunctionResponse { name, response }),
+                        ..Default::default()
+                    }],
+                )
+            }
+            role => {
+                let mut parts = Vec::new();
+                let text = message.text();
+                if !text.is_empty() {
+                    parts.push(GeminiPart::text(&text));
+                }
+
+                for image in message.images() {
+                    match image {
+                        ImageSource::Base64 { media_type, data } => parts.push(GeminiPart {
+                            inline_data: Some(GeminiInlineData {
+                                mime_type: media_type.clone(),
+                                data: data.clone(),
+                            }),
+                            ..Default::default()
+                        }),
+                        ImageSource::Url { url } => {
+                            return Err(WebApiClientError::InvalidInput(format!(
+                                "Gemini only accepts base64 images, got URL {url}"
+                            )));
+                        }
+                    }
+                }
+
+                parts.extend(message.tool_calls.iter().map(|call| GeminiPart {
+                    function_call: Some(GeminiFunctionCall {
+                        name: call.name.clone(),
+                        args: call.arguments.clone(),
+                    }),
+                    ..Default::default()
+                }));
+
+                let role = if role == Role::Assistant {
+                    "model"
+                } else {
+                    "user"
+                };
+                (role, parts)
+            }
+        };
+
+        if parts.is_empty() {
+            continue;
+        }
+
+        match contents.last_mut() {
+            Some(last) if last.role == role => last.parts.extend(parts),
+            _ => contents.push(GeminiContent {
+                role: role.to_string(),
+                parts,
+            }),
+        }
+    }
+
+    let system = if system.is_empty() {
+        None
+    } else {
+        Some(GeminiContent {
+            role: String::new(),
+            parts: system,
+        })
+    };
+
+    Ok((system, contents))
+}
+
+// function responses are matched by name, so find the call being answered
+fn tool_call_name(
+    previous: &[ChatMessage],
+    tool_call_id: Option<&str>,
+) -> Result<String, WebApiClientError> {
+    previous
+        .iter()
+        .rev()
+        .flat_map(|message| &message.tool_calls)
+        .find(|call| Some(call.id.as_str()) == tool_call_id)
+        .map(|call| call.name.clone())
+        .ok_or_else(|| {
+            WebApiClientError::InvalidInput(format!(
+                "Tool result {} answers no earlier tool call",
+                tool_call_id.unwrap_or("without tool_call_id")
+            ))
+        })
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_json_schema: Option<Value>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentRequest {
+    contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<SafetySetting>,
+    generation_config: GenerationConfig,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    pub prompt_token_count: Option<usize>,
+    pub candidates_token_count: Option<usize>,
+    pub total_token_count: Option<usize>,
+}
+
+impl From<UsageMetadata> for Usage {
+    fn from(usage: UsageMetadata) -> Self {
+        Usage {
+            prompt_tokens: usage.prompt_token_count,
+            completion_tokens: usage.candidates_token_count,
+            total_tokens: usage.total_token_count,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidate {
+    pub content: Option<GeminiContent>,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptFeedback {
+    pub block_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateContentResponse {
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+    pub usage_metadata: Option<UsageMetadata>,
+    #[serde(default)]
+    pub model_version: String,
+    pub prompt_feedback: Option<PromptFeedback>,
+}
+
+impl GenerateContentResponse {
+    fn parts(&self) -> impl Iterator<Item = &GeminiPart> {
+        self.candidates
+            .first()
+            .and_then(|candidate| candidate.content.as_ref())
+            .into_iter()
+            .flat_map(|content| &content.parts)
+            .filter(|part| !part.thought)
+    }
+
+    /// The answer text of the first candidate, without thought summaries.
+    pub fn text(&self) -> String {
+        self.parts()
+            .filter_map(|part| part.text.as_deref())
+            .collect()
+    }
+
+    pub fn finish_reason(&self) -> Option<&str> {
+        self.candidates
+            .first()
+            .and_then(|candidate| candidate.finish_reason.as_deref())
+    }
+
+    fn check_blocked(self) -> Result<Self, WebApiClientError> {
+        match &self.prompt_feedback {
+            Some(PromptFeedback {
+                block_reason: Some(reason),
+            }) if self.candidates.is_empty() => Err(WebApiClientError::PostFailed(format!(
+                "Gemini blocked the prompt: {reason}"
+            ))),
+            _ => Ok(self),
+        }
+    }
+}
+
+impl From<GenerateContentResponse> for LlmResponse {
+    fn from(response: GenerateContentResponse) -> Self {
+        LlmResponse {
+            model: response.model_version.clone(),
+            content: response.text(),
+            finish_reason: response.finish_reason().map(str::to_string),
+            tool_calls: response
+                .parts()
+                .filter_map(|part| part.function_call.as_ref())
+                .map(|call| ToolCall {
+                    // Gemini does not identify function calls, so give them ids for the reply
+                    id: format!("call_{}", Uuid::new_v4().simple()),
+                    name: call.name.clone(),
+                    arguments: call.args.clone(),
+                })
+                .collect(),
+            usage: response.usage_metadata.map(Usage::from),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct BatchEmbedResponse {
+    embeddings: Vec<EmbeddingValues>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingValues {
+    values: Vec<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsResponse {
+    #[serde(default)]
+    models: Vec<ModelsData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsData {
+    name: String,
+}
+
+pub struct GeminiClient {
+    auth_api_client: WebApiClient,
+    base_url: Url,
+    max_tokens: Option<usize>,
+    safety_settings: Vec<SafetySetting>,
+}
+
+impl GeminiClient {
+    /// Clients given the same `concurrency_limiter` share its limit.
+    pub fn new(
+        setting: &ServerConfig,
+        api_key: Option<String>,
+        concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
+    ) -> Result<Self, WebApiClientError> {
+        let api_key = match api_key {
+            Some(api_key) if !api_key.is_empty() => api_key,
+            _ => {
+                return Err(WebApiClientError::InvalidApiKey(
+                    "API key cannot be empty".to_string(),
+                ));
+            }
+        };
+
+        let mut auth_api_client = WebApiClient::for_server(setting, concurrency_limiter);
+
+        // sent as a header rather than the `key` query parameter, which would
+        // end up in logged URLs
+        if let Err(e) = auth_api_client.add_header("x-goog-api-key", api_key) {
+            return Err(WebApiClientError::InvalidApiKey(format!(
+                "Failed to add header to WebApiClient: {e}"
+            )));
+        }
+
+        let base_url = match Url::parse(&setting.base_api_url) {
+            Ok(url) => url,
+            Err(e) => {
+                return Err(WebApiClientError::InvalidInput(format!(
+                    "Failed to parse base API URL ({}): {}",
+                    setting.base_api_url, e
+                )));
+            }
+        };
+
+        Ok(Self {
+            auth_api_client,
+            base_url,
+            max_tokens: setting.max_tokens,
+            safety_settings: setting.safety_settings.clone(),
+        })
+    }
+
+    /// `path` below the API root.
+    fn url(&self, path: &str) -> Result<Url, WebApiClientError> {
+        join_url(&self.base_url, &format!("v1beta/{path}"))
+    }
+
+    fn request(
+        &self,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+        json: bool,
+        schema: Option<&Value>,
+    ) -> Result<GenerateContentRequest, WebApiClientError> {
+        let (system_instruction, contents) = to_gemini_contents(messages)?;
+
+        let tools = if tools.is_empty() {
+            Vec::new()
+        } else {
+            let declarations: Vec<Value> = tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    })
+                })
+                .collect();
+            vec![json!({ "functionDeclarations": declarations })]
+        };
+
+        let response_mime_type = if json || schema.is_some() {
+            Some("application/json".to_string())
+        } else {
+            None
+        };
+
+        Ok(GenerateContentRequest {
+            contents,
+            system_instruction,
+            tools,
+            safety_settings: self.safety_settings.clone(),
+            generation_config: GenerationConfig {
+                max_output_tokens: self.max_tokens,
+                response_mime_type,
+                response_json_schema: schema.cloned(),
+            },
+        })
+    }
+
+    pub async fn generate_content(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+        json: bool,
+    ) -> Result<GenerateContentResponse, WebApiClientError> {
+        let request = self.request(messages, tools, json, None)?;
+        self.send_generate_content(model, request).await
+    }
+
+    /// Generation constrained to the given JSON Schema.
+    pub async fn generate_content_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<GenerateContentResponse, WebApiClientError> {
+        let request = self.request(messages, &[], true, Some(schema))?;
+        self.send_generate_content(model, request).await
+    }
+
+    async fn send_generate_content(
+        &self,
+        model: &str,
+        request: GenerateContentRequest,
+    ) -> Result<GenerateContentResponse, WebApiClientError> {
+        let url = self.url(&format!("models/{model}:generateContent"))?;
+
+        let json_value = self
+            .auth_api_client
+            .post_request(url, &json!(request))
+            .await?;
+
+        let parsed: GenerateContentResponse = match serde_json::from_value(json_value) {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(WebApiClientError::ParseError(format!(
+                    "Failed to parse generateContent response: {e}"
+                )));
+            }
+        };
+
+        parsed.check_blocked()
+    }
+
+    /// Streams partial responses. Each chunk holds the next piece of text;
+    /// the last one carries the finish reason and `usageMetadata`.
+    pub async fn stream_generate_content(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<
+        BoxStream<'static, Result<GenerateContentResponse, WebApiClientError>>,
+        WebApiClientError,
+    > {
+        let mut url = self.url(&format!("models/{model}:streamGenerateContent"))?;
+        url.query_pairs_mut().append_pair("alt", "sse");
+
+        let request = self.request(messages, tools, false, None)?;
+        let events = self
+            .auth_api_client
+            .post_event_stream(url, &json!(request))
+            .await?;
+
+        Ok(events
+            .map(|event| {
+                let event = event?;
+                let chunk: GenerateContentResponse =
+                    serde_json::from_str(&event.data).map_err(|e| {
+                        WebApiClientError::ParseError(format!(
+                            "Failed to parse streamGenerateContent chunk: {e}"
+                        ))
+                    })?;
+                chunk.check_blocked()
+            })
+            .boxed())
+    }
+
+    /// Embeds many inputs per request through `batchEmbedContents`.
+    pub async fn embeddings(
+        &self,
+        model: &str,
+        input: &[String],
+        options: &EmbedOptions,
+    ) -> Result<EmbedResponse, WebApiClientError> {
+        let url = self.url(&format!("models/{model}:batchEmbedContents"))?;
+
+        let mut response = EmbedResponse {
+            model: model.to_string(),
+            ..Default::default()
+        };
+
+        for batch in input.chunks(options.batch_size()) {
+            let requests: Vec<Value> = batch
+                .iter()
+                .map(|text| {
+                    let mut request = json!({
+                        "model": format!("models/{model}"),
+                        "content": { "parts": [{ "text": text }] },
+                    });
+                    if let Some(dimensions) = options.dimensions {
+                        request["outputDimensionality"] = json!(dimensions);
+                    }
+                    request
+                })
+                .collect();
+
+            let json_value = self
+                .auth_api_client
+                .post_request(url.clone(), &json!({ "requests": requests }))
+                .await?;
+
+            let parsed: BatchEmbedResponse = match serde_json::from_value(json_value) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    return Err(WebApiClientError::ParseError(format!(
+                        "Failed to parse batchEmbedContents response: {e}"
+                    )));
+                }
+            };
+
+            if parsed.embeddings.len() != batch.len() {
+                return Err(WebApiClientError::ParseError(format!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    parsed.embeddings.len()
+                )));
+            }
+
+            response.extend(EmbedResponse {
+                model: model.to_string(),
+                embeddings: parsed.embeddings.into_iter().map(|e| e.values).collect(),
+                usage: None,
+            });
+        }
+
+        if options.normalize {
+            response.normalize();
+        }
+
+        Ok(response)
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, WebApiClientError> {
+        let url = self.url("models")?;
+
+        let json_value = self.auth_api_client.get_request(url).await?;
+
+        let parsed: ModelsResponse = match serde_json::from_value(json_value) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return Err(WebApiClientError::ParseError(format!(
+                    "Failed to parse models response: {e}"
+                )));
+            }
+        };
+
+        Ok(parsed
+            .models
+            .into_iter()
+            .map(|model| ModelInfo {
+                name: model
+                    .name
+                    .strip_prefix("models/")
+                    .unwrap_or(&model.name)
+                    .to_string(),
+                owned_by: Some("google".to_string()),
+                size: None,
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl LlmClient for GeminiClient {
+    async fn generate(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let messages = [
+            ChatMessage::system(system_prompt),
+            ChatMessage::user(prompt),
+        ];
+
+        self.chat(model, &messages, json).await
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = self.generate_content(model, messages, &[], json).await?;
+        Ok(response.into())
+    }
+
+    async fn chat_with_tools(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = self.generate_content(model, messages, tools, false).await?;
+        Ok(response.into())
+    }
+
+    async fn chat_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = self
+            .generate_content_structured(model, messages, schema)
+            .await?;
+        Ok(response.into())
+    }
+
+    async fn embed(
+        &self,
+        model: &str,
+        input: &[String],
+        options: &EmbedOptions,
+    ) -> Result<EmbedResponse, LlmClientError> {
+        Ok(self.embeddings(model, input, options).await?)
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmClientError> {
+        Ok(GeminiClient::list_models(self).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(id: &str, name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn extracts_system_instruction() {
+        let messages = [
+            ChatMessage::system("Be brief."),
+            ChatMessage::user("Hi"),
+            ChatMessage::system("Answer in French."),
+        ];
+
+        let (system, contents) = to_gemini_contents(&messages).unwrap();
+
+        assert_eq!(
+            system,
+            Some(GeminiContent {
+                role: String::new(),
+                parts: vec![
+                    GeminiPart::text("Be brief."),
+                    GeminiPart::text("Answer in French.")
+                ],
+            })
+        );
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[0].role, "user");
+
+        let (system, _) = to_gemini_contents(&[ChatMessage::user("Hi")]).unwrap();
+        assert_eq!(system, None);
+    }
+
+    #[test]
+    fn maps_roles_and_merges_consecutive_turns() {
+        let messages = [
+            ChatMessage::user("One"),
+            ChatMessage::user("Two"),
+            ChatMessage::assistant("Three"),
+            ChatMessage::user("Four"),
+        ];
+
+        let (_, contents) = to_gemini_contents(&messages).unwrap();
+
+        let roles: Vec<_> = contents.iter().map(|c| c.role.as_str()).collect();
+        assert_eq!(roles, ["user", "model", "user"]);
+        assert_eq!(
+            contents[0].parts,
+            [GeminiPart::text("One"), GeminiPart::text("Two")]
+        );
+    }
+
+    #[test]
+    fn pairs_function_calls_with_responses() {
+        let messages = [
+            ChatMessage::user("Weather in Oslo and Rome?"),
+            ChatMessage::assistant("").with_tool_calls(vec![
+                call("call-1", "weather", json!({ "city": "Oslo" })),
+                call("call-2", "forecast", json!({ "city": "Rome" })),
+            ]),
+            ChatMessage::tool("call-2", r#"{"sky": "clear"}"#),
+            ChatMessage::tool("call-1", "rain"),
+        ];
+
+        let (_, contents) = to_gemini_contents(&messages).unwrap();
+        let json = serde_json::to_value(&contents).unwrap();
+
+        assert_eq!(
+            json,
+            json!([
+                { "role": "user", "parts": [{ "text": "Weather in Oslo and Rome?" }] },
+                { "role": "model", "parts": [
+                    { "functionCall": { "name": "weather", "args": { "city": "Oslo" } } },
+                    { "functionCall": { "name": "forecast", "args": { "city": "Rome" } } },
+                ] },
+                { "role": "user", "parts": [
+                    { "functionResponse": { "name": "forecast", "response": { "sky": "clear" } } },
+                    { "functionResponse": { "name": "weather", "response": { "content": "rain" } } },
+                ] },
+            ])
+        );
+    }
+
+    #[test]
+    fn tool_result_name_takes_precedence() {
+        let messages = [
+            ChatMessage::assistant("").with_tool_calls(vec![call("call-1", "weather", json!({}))]),
+            ChatMessage::tool("call-1", "rain").with_name("get_weather"),
+        ];
+
+        let (_, contents) = to_gemini_contents(&messages).unwrap();
+        let response = contents[1].parts[0].function_response.as_ref().unwrap();
+        assert_eq!(response.name, "get_weather");
+    }
+
+    #[test]
+    fn unmatched_tool_result_is_an_error() {
+        let messages = [
+            ChatMessage::assistant("").with_tool_calls(vec![call("call-1", "weather", json!({}))]),
+            ChatMessage::tool("call-9", "rain"),
+        ];
+
+        let error = LlmClientError::from(to_gemini_contents(&messages).unwrap_err());
+        match error {
+            LlmClientError::InvalidInput(message) => assert!(message.contains("call-9")),
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn sends_base64_images_inline() {
+        let messages =
+            [ChatMessage::user("What is this?").with_image_base64("image/png", "iVBORw0KGgo=")];
+
+        let (_, contents) = to_gemini_contents(&messages).unwrap();
+
+        assert_eq!(
+            contents[0].parts,
+            [
+                GeminiPart::text("What is this?"),
+                GeminiPart {
+                    inline_data: Some(GeminiInlineData {
+                        mime_type: "image/png".to_string(),
+                        data: "iVBORw0KGgo=".to_string(),
+                    }),
+                    ..Default::default()
+                },
+            ]
+        );
+
+        let messages = [ChatMessage::user("And this?").with_image_url("https://example.com/a.png")];
+        assert!(matches!(
+            to_gemini_contents(&messages),
+            Err(WebApiClientError::InvalidInput(_))
+        ));
+    }
+}
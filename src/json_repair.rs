@@ -0,0 +1,129 @@
+/// Best-effort cleanup of JSON produced by a model: strips markdown fences and
+/// surrounding prose, drops trailing commas, and closes strings and brackets
+/// left open by a truncated response. Text without any JSON is returned trimmed.
+pub fn repair_json(text: &str) -> String {
+    let text = strip_fences(text.trim());
+
+    let start = match text.find(['{', '[']) {
+        Some(start) => start,
+        None => return text.to_string(),
+    };
+
+    let mut output = String::with_capacity(text.len());
+    let mut closers: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text[start..].chars() {
+        if in_string {
+            output.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                output.push(c);
+            }
+            '{' => {
+                closers.push('}');
+                output.push(c);
+            }
+            '[' => {
+                closers.push(']');
+                output.push(c);
+            }
+            '}' | ']' => {
+                remove_trailing_comma(&mut output);
+                closers.pop();
+                output.push(c);
+                if closers.is_empty() {
+                    // anything after the top-level value is prose
+                    return output;
+                }
+            }
+            _ => output.push(c),
+        }
+    }
+
+    // the response was cut off, close whatever is still open
+    if in_string {
+        if escaped {
+            output.pop();
+        }
+        output.push('"');
+    }
+
+    let trimmed_len = output.trim_end().len();
+    output.truncate(trimmed_len);
+    if output.ends_with(':') {
+        output.push_str(" null");
+    }
+    remove_trailing_comma(&mut output);
+
+    while let Some(closer) = closers.pop() {
+        output.push(closer);
+    }
+
+    output
+}
+
+fn strip_fences(text: &str) -> &str {
+    let start = match text.find("```") {
+        Some(start) => start,
+        None => return text,
+    };
+
+    // skip the opening fence and its language tag
+    let after = &text[start + 3..];
+    let body = match after.find('\n') {
+        Some(newline) => &after[newline + 1..],
+        None => after,
+    };
+
+    match body.find("```") {
+        Some(end) => body[..end].trim(),
+        None => body.trim(),
+    }
+}
+
+fn remove_trailing_comma(output: &mut String) {
+    let trimmed_len = output.trim_end().len();
+    if output[..trimmed_len].ends_with(',') {
+        output.truncate(trimmed_len - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repairs_model_json() {
+        let cases = [
+            ("{\"a\": 1}", "{\"a\": 1}"),
+            ("```json\n{\"a\": 1}\n```", "{\"a\": 1}"),
+            ("Here you go:\n```\n[1, 2]\n```\nThanks!", "[1, 2]"),
+            ("{\"a\": [1, 2,], }", "{\"a\": [1, 2]}"),
+            ("{\"a\": \"trunc", "{\"a\": \"trunc\"}"),
+            ("{\"a\": \"esc\\", "{\"a\": \"esc\"}"),
+            ("[1, 2, [3,", "[1, 2, [3]]"),
+            ("{\"a\": {\"b\": 1,", "{\"a\": {\"b\": 1}}"),
+            ("{\"a\":", "{\"a\": null}"),
+            ("{\"a\": 1} and some notes {x}", "{\"a\": 1}"),
+            ("Sure! {\"a\": \"}\"} done", "{\"a\": \"}\"}"),
+            ("no json here  ", "no json here"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(repair_json(input), expected, "input: {input:?}");
+        }
+    }
+}
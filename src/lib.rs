@@ -1,5 +1,27 @@
+pub mod agent;
+pub mod anthropic_client;
+pub mod chat_message;
+pub mod client_factory;
+pub mod concurrency_limiter;
+pub mod conversation;
+pub mod endpoint_runner;
+pub mod error;
+pub mod gemini_client;
+pub mod json_repair;
+pub mod llm_client;
 pub mod ollama_client;
 pub mod openai_client;
+pub mod prompt_template;
+pub mod rag;
+pub mod rate_limiter;
 pub mod secrets;
 pub mod settings;
+pub mod sse;
+pub mod streaming;
+pub mod structured;
+pub mod text_splitter;
+pub mod tool;
+pub mod vector_store;
 pub mod web_api_client;
+
+pub use error::{Error, Result};
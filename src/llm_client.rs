@@ -0,0 +1,347 @@
+pub use crate::chat_message::ChatMessage;
+use crate::chat_message::ToolCall;
+use crate::openai_client::OpenAiClientError;
+use crate::prompt_template::PromptTemplateError;
+use crate::tool::Tool;
+use crate::vector_store::VectorStoreError;
+use crate::web_api_client::{HttpError, WebApiClientError};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Display;
+
+pub const DEFAULT_EMBED_BATCH_SIZE: usize = 64;
+
+#[derive(Debug)]
+pub enum LlmClientError {
+    InvalidApiKey(String),
+    InvalidInput(String),
+    RequestFailed(String),
+    ParseError(String),
+    Unsupported(String),
+    MaxStepsReached(usize),
+    SchemaMismatch(String),
+    ConnectionFailed(String),
+    Http(Box<HttpError>),
+    Overloaded(String),
+    /// Any other failure of the underlying HTTP client.
+    WebApi(WebApiClientError),
+    /// Reading or searching a vector store failed.
+    VectorStore(VectorStoreError),
+}
+
+impl Display for LlmClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LlmClientError::InvalidApiKey(msg) => write!(f, "Invalid API key: {msg}"),
+            LlmClientError::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
+            LlmClientError::RequestFailed(msg) => write!(f, "Request failed: {msg}"),
+            LlmClientError::ParseError(msg) => write!(f, "Parse error: {msg}"),
+            LlmClientError::Unsupported(msg) => write!(f, "Unsupported: {msg}"),
+            LlmClientError::MaxStepsReached(steps) => {
+                write!(f, "No final answer after {steps} steps")
+            }
+            LlmClientError::SchemaMismatch(msg) => write!(f, "Schema mismatch: {msg}"),
+            LlmClientError::ConnectionFailed(msg) => write!(f, "Connection failed: {msg}"),
+            LlmClientError::Http(_) => write!(f, "Error response from server"),
+            LlmClientError::Overloaded(msg) => write!(f, "Server overloaded: {msg}"),
+            LlmClientError::WebApi(_) => write!(f, "Request failed"),
+            LlmClientError::VectorStore(_) => write!(f, "Vector store error"),
+        }
+    }
+}
+
+impl std::error::Error for LlmClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LlmClientError::Http(error) => Some(error.as_ref()),
+            LlmClientError::WebApi(error) => Some(error),
+            LlmClientError::VectorStore(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl LlmClientError {
+    pub fn http(&self) -> Option<&HttpError> {
+        match self {
+            LlmClientError::Http(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LlmClientError::ConnectionFailed(_) | LlmClientError::Overloaded(_) => true,
+            LlmClientError::Http(error) => error.is_retryable(),
+            _ => false,
+        }
+    }
+
+    pub fn is_auth(&self) -> bool {
+        match self {
+            LlmClientError::InvalidApiKey(_) => true,
+            LlmClientError::Http(error) => error.is_auth(),
+            _ => false,
+        }
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, LlmClientError::Http(error) if error.is_rate_limited())
+    }
+}
+
+impl From<WebApiClientError> for LlmClientError {
+    fn from(error: WebApiClientError) -> Self {
+        match error {
+            WebApiClientError::InvalidApiKey(msg) => LlmClientError::InvalidApiKey(msg),
+            WebApiClientError::InvalidInput(msg) => LlmClientError::InvalidInput(msg),
+            WebApiClientError::ParseError(msg) => LlmClientError::ParseError(msg),
+            WebApiClientError::ConnectionFailed(msg) => LlmClientError::ConnectionFailed(msg),
+            WebApiClientError::Http(error) => LlmClientError::Http(error),
+            WebApiClientError::Overloaded(msg) => LlmClientError::Overloaded(msg),
+            other => LlmClientError::WebApi(other),
+        }
+    }
+}
+
+impl From<OpenAiClientError> for LlmClientError {
+    fn from(error: OpenAiClientError) -> Self {
+        match error {
+            OpenAiClientError::InvalidApiKey(msg) => LlmClientError::InvalidApiKey(msg),
+            OpenAiClientError::InvalidInput(msg) => LlmClientError::InvalidInput(msg),
+            OpenAiClientError::CompletionFailed(msg) => LlmClientError::RequestFailed(msg),
+            OpenAiClientError::RequestFailed(error) => LlmClientError::from(error),
+        }
+    }
+}
+
+impl From<PromptTemplateError> for LlmClientError {
+    fn from(error: PromptTemplateError) -> Self {
+        LlmClientError::InvalidInput(error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: Option<usize>,
+    pub completion_tokens: Option<usize>,
+    pub total_tokens: Option<usize>,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: Option<usize>, completion_tokens: Option<usize>) -> Self {
+        let total_tokens = match (prompt_tokens, completion_tokens) {
+            (Some(prompt), Some(completion)) => Some(prompt + completion),
+            _ => None,
+        };
+
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens,
+        }
+    }
+
+    pub fn add(&self, other: &Usage) -> Usage {
+        let sum = |a: Option<usize>, b: Option<usize>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+
+        Usage {
+            prompt_tokens: sum(self.prompt_tokens, other.prompt_tokens),
+            completion_tokens: sum(self.completion_tokens, other.completion_tokens),
+            total_tokens: sum(self.total_tokens, other.total_tokens),
+        }
+    }
+}
+
+/// A completed generation or chat turn, independent of the backend that produced it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LlmResponse {
+    pub model: String,
+    pub content: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl LlmResponse {
+    /// The reply as an assistant message, ready to append to a conversation.
+    pub fn message(&self) -> ChatMessage {
+        ChatMessage::assistant(&self.content).with_tool_calls(self.tool_calls.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbedOptions {
+    /// Ask for vectors of this size, for models that support shortening them.
+    pub dimensions: Option<usize>,
+    /// Scale every vector to unit length before returning it.
+    pub normalize: bool,
+    /// Inputs sent per request. Defaults to `DEFAULT_EMBED_BATCH_SIZE`.
+    pub batch_size: Option<usize>,
+}
+
+impl EmbedOptions {
+    pub fn batch_size(&self) -> usize {
+        self.batch_size.unwrap_or(DEFAULT_EMBED_BATCH_SIZE).max(1)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbedResponse {
+    pub model: String,
+    pub embeddings: Vec<Vec<f32>>,
+    pub usage: Option<Usage>,
+}
+
+impl EmbedResponse {
+    /// Appends the vectors of a later batch, summing token usage.
+    pub fn extend(&mut self, batch: EmbedResponse) {
+        if self.model.is_empty() {
+            self.model = batch.model;
+        }
+        self.embeddings.extend(batch.embeddings);
+        self.usage = match (self.usage.take(), batch.usage) {
+            (Some(usage), Some(other)) => Some(usage.add(&other)),
+            (usage, other) => usage.or(other),
+        };
+    }
+
+    pub fn normalize(&mut self) {
+        for vector in &mut self.embeddings {
+            let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|v| *v /= norm);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub name: String,
+    pub owned_by: Option<String>,
+    pub size: Option<u64>,
+}
+
+/// Operations every backend supports, so application code can hold a
+/// `Box<dyn LlmClient>` without caring which provider is behind it.
+#[async_trait]
+pub trait LlmClient: Send + Sync {
+    async fn generate(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError>;
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError>;
+
+    /// Like `chat`, advertising `tools` to the model. Requested calls are
+    /// returned in `LlmResponse::tool_calls`.
+    async fn chat_with_tools(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<LlmResponse, LlmClientError>;
+
+    /// Like `chat`, asking the backend to constrain its reply to the JSON
+    /// `schema`. See `StructuredOutput` for typed results.
+    async fn chat_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<LlmResponse, LlmClientError>;
+
+    /// Embeds every input in order, in batches of `options.batch_size()`.
+    async fn embed(
+        &self,
+        model: &str,
+        input: &[String],
+        options: &EmbedOptions,
+    ) -> Result<EmbedResponse, LlmClientError>;
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmClientError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use reqwest::header::HeaderMap;
+
+    fn http_error(status: u16) -> LlmClientError {
+        WebApiClientError::Http(Box::new(HttpError::new(
+            StatusCode::from_u16(status).unwrap(),
+            HeaderMap::new(),
+            r#"{"error": {"message": "failed"}}"#.to_string(),
+        )))
+        .into()
+    }
+
+    #[test]
+    fn classifies_http_statuses() {
+        // (status, retryable, auth, rate limited)
+        let cases = [
+            (400, false, false, false),
+            (401, false, true, false),
+            (403, false, true, false),
+            (408, true, false, false),
+            (429, true, false, true),
+            (500, true, false, false),
+            (503, true, false, false),
+        ];
+
+        for (status, retryable, auth, rate_limited) in cases {
+            let error = http_error(status);
+            assert!(matches!(error, LlmClientError::Http(_)), "{status}");
+            assert_eq!(error.is_retryable(), retryable, "{status}");
+            assert_eq!(error.is_auth(), auth, "{status}");
+            assert_eq!(error.is_rate_limited(), rate_limited, "{status}");
+        }
+    }
+
+    #[test]
+    fn classifies_other_errors() {
+        let error = LlmClientError::from(WebApiClientError::ConnectionFailed("timeout".into()));
+        assert!(error.is_retryable());
+        assert!(!error.is_auth());
+
+        let error = LlmClientError::from(WebApiClientError::Overloaded("queue full".into()));
+        assert!(error.is_retryable());
+
+        let error = LlmClientError::from(WebApiClientError::InvalidApiKey("missing".into()));
+        assert!(error.is_auth());
+        assert!(!error.is_retryable());
+
+        let error = LlmClientError::ParseError("bad json".into());
+        assert!(!error.is_retryable());
+        assert!(!error.is_auth());
+        assert!(!error.is_rate_limited());
+    }
+
+    #[test]
+    fn http_error_is_kept_as_source() {
+        use std::error::Error;
+
+        let error = http_error(429);
+        let source = error.source().unwrap().to_string();
+        assert_eq!(
+            source,
+            "Server returned error status 429 Too Many Requests: failed"
+        );
+    }
+}
@@ -1,9 +1,26 @@
-use crate::settings::ServerConfig;
-use crate::web_api_client::{WebApiClient, WebApiClientError};
-use log::debug;
+use crate::chat_message::{ImageSource, Role, ToolCall};
+use crate::concurrency_limiter::ConcurrencyLimiter;
+use crate::llm_client::{
+    ChatMessage, EmbedOptions, EmbedResponse, LlmClient, LlmClientError, LlmResponse, ModelInfo,
+    Usage,
+};
+use crate::settings::{EndpointPaths, ServerConfig};
+use crate::streaming::json_lines;
+use crate::tool::Tool;
+use crate::web_api_client::{WebApiClient, WebApiClientError, join_url};
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{Value, json};
+use std::sync::Arc;
 use url::Url;
+use uuid::Uuid;
+
+const GENERATE_PATH: &str = "api/generate";
+const CHAT_PATH: &str = "api/chat";
+const EMBED_PATH: &str = "api/embed";
+const TAGS_PATH: &str = "api/tags";
 
 #[derive(Debug, Serialize)]
 struct GenerateRequest {
@@ -14,7 +31,7 @@ struct GenerateRequest {
     stream: bool,
     temperature: Option<f32>,
     suffix: Option<String>,
-    format: Option<String>,
+    format: Option<Value>,
     keep_alive: Option<String>,
 }
 
@@ -42,6 +59,8 @@ pub struct GenerateResponse {
     pub response: String,
     pub done: bool,
     pub done_reason: Option<String>,
+    // only sent with the final chunk of a streamed response
+    #[serde(default)]
     pub context: Vec<usize>,
     pub total_duration: Option<u64>,
     pub load_duration: Option<u64>,
@@ -51,39 +70,181 @@ pub struct GenerateResponse {
     pub eval_duration: Option<u64>,
 }
 
+/// A chat message in the Ollama wire format: plain text content, base64
+/// images alongside it, and tool arguments as a JSON object.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OllamaMessage {
+    pub role: Role,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<OllamaToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OllamaToolCall {
+    pub function: OllamaFunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OllamaFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+impl TryFrom<&ChatMessage> for OllamaMessage {
+    type Error = WebApiClientError;
+
+    fn try_from(message: &ChatMessage) -> Result<Self, Self::Error> {
+        let mut images = Vec::new();
+        for image in message.images() {
+            match image {
+                ImageSource::Base64 { data, .. } => images.push(data.clone()),
+                ImageSource::Url { url } => {
+                    return Err(WebApiClientError::InvalidInput(format!(
+                        "Ollama only accepts base64 images, got URL {url}"
+                    )));
+                }
+            }
+        }
+
+        let tool_name = match message.role {
+            Role::Tool => message.name.clone(),
+            _ => None,
+        };
+
+        Ok(OllamaMessage {
+            role: message.role,
+            content: message.text(),
+            images,
+            tool_calls: message
+                .tool_calls
+                .iter()
+                .map(|call| OllamaToolCall {
+                    function: OllamaFunctionCall {
+                        name: call.name.clone(),
+                        // Ollama wants an object, other providers may have sent a string
+                        arguments: match &call.arguments {
+                            Value::String(arguments) => serde_json::from_str(arguments)
+                                .unwrap_or_else(|_| call.arguments.clone()),
+                            arguments => arguments.clone(),
+                        },
+                    },
+                })
+                .collect(),
+            tool_name,
+        })
+    }
+}
+
+impl From<OllamaMessage> for ChatMessage {
+    fn from(message: OllamaMessage) -> Self {
+        let mut chat_message = ChatMessage::new(message.role, &message.content);
+
+        // Ollama does not say what format its images are in
+        for image in &message.images {
+            chat_message = chat_message.with_image_base64("image/png", image);
+        }
+
+        chat_message.tool_calls = message
+            .tool_calls
+            .into_iter()
+            .map(|call| ToolCall {
+                // Ollama does not identify tool calls, so give them ids for the reply
+                id: format!("call_{}", Uuid::new_v4().simple()),
+                name: call.function.name,
+                arguments: call.function.arguments,
+            })
+            .collect();
+
+        chat_message
+    }
+}
+
+fn deserialize_message<'de, D>(deserializer: D) -> Result<ChatMessage, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    OllamaMessage::deserialize(deserializer).map(ChatMessage::from)
+}
+
+#[derive(Debug, Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    stream: bool,
+    format: Option<Value>,
+    keep_alive: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatResponse {
+    pub model: String,
+    pub created_at: String,
+    #[serde(deserialize_with = "deserialize_message")]
+    pub message: ChatMessage,
+    pub done: bool,
+    pub done_reason: Option<String>,
+    pub total_duration: Option<u64>,
+    pub load_duration: Option<u64>,
+    pub prompt_eval_count: Option<usize>,
+    pub prompt_eval_duration: Option<u64>,
+    pub eval_count: Option<usize>,
+    pub eval_duration: Option<u64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct EmbeddingRequest {
     pub model: String,
-    pub prompt: String,
+    pub input: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+    pub keep_alive: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct EmbeddingResponse {
-    pub _embedding: Vec<f32>,
+    #[serde(default)]
+    pub model: String,
+    pub embeddings: Vec<Vec<f32>>,
+    pub prompt_eval_count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    models: Vec<TagsModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsModel {
+    name: String,
+    size: Option<u64>,
 }
 
 #[derive(Debug)]
 pub struct OllamaClient {
     auth_api_client: WebApiClient,
     base_url: Url,
+    paths: EndpointPaths,
 }
 
 impl OllamaClient {
-    pub fn new(setting: &ServerConfig, api_key: Option<String>) -> Result<Self, WebApiClientError> {
+    /// Clients given the same `concurrency_limiter` share its limit.
+    pub fn new(
+        setting: &ServerConfig,
+        api_key: Option<String>,
+        concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
+    ) -> Result<Self, WebApiClientError> {
         let api_key: String = api_key.unwrap_or_default();
 
-        debug!(
-            "Setting Connection Timeout: {}",
-            setting.connection_timeout.unwrap_or(u64::MAX)
-        );
-
-        debug!(
-            "Setting Deadline Timeout: {}",
-            setting.deadline_timeout.unwrap_or(u64::MAX)
-        );
-
-        let mut auth_api_client =
-            WebApiClient::new(setting.connection_timeout, setting.deadline_timeout);
+        let mut auth_api_client = WebApiClient::for_server(setting, concurrency_limiter);
 
         match auth_api_client.add_header("Authorization", format!("Bearer {api_key}")) {
             Ok(client) => client,
@@ -107,6 +268,7 @@ impl OllamaClient {
         Ok(Self {
             auth_api_client,
             base_url,
+            paths: setting.paths.clone(),
         })
     }
 
@@ -117,14 +279,12 @@ impl OllamaClient {
         prompt: &str,
         json: bool,
     ) -> Result<GenerateResponse, WebApiClientError> {
-        let format = if json { Some("json".to_string()) } else { None };
+        let format = if json { Some(json!("json")) } else { None };
 
-        let url = match self.base_url.join("/api/generate") {
-            Ok(url) => url,
-            Err(e) => {
-                return Err(WebApiClientError::InvalidInput(format!("Invalid URL: {e}")));
-            }
-        };
+        let url = join_url(
+            &self.base_url,
+            self.paths.generate.as_deref().unwrap_or(GENERATE_PATH),
+        )?;
 
         let json_value = self
             .auth_api_client
@@ -152,38 +312,401 @@ impl OllamaClient {
         Ok(parsed)
     }
 
-    pub async fn _embeddings(
+    pub async fn chat(
         &self,
         model: &str,
-        text: &str,
-    ) -> Result<EmbeddingResponse, WebApiClientError> {
-        let url = match self.base_url.join("/api/embeddings") {
-            Ok(url) => url,
+        messages: &[ChatMessage],
+        json: bool,
+    ) -> Result<ChatResponse, WebApiClientError> {
+        let format = if json { Some(json!("json")) } else { None };
+        self.send_chat(model, messages, &[], format).await
+    }
+
+    pub async fn chat_with_tools(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<ChatResponse, WebApiClientError> {
+        self.send_chat(model, messages, tools, None).await
+    }
+
+    /// Chat constrained to the given JSON Schema, sent as Ollama's `format` object.
+    pub async fn chat_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<ChatResponse, WebApiClientError> {
+        self.send_chat(model, messages, &[], Some(schema.clone()))
+            .await
+    }
+
+    async fn send_chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+        format: Option<Value>,
+    ) -> Result<ChatResponse, WebApiClientError> {
+        let url = join_url(
+            &self.base_url,
+            self.paths.chat.as_deref().unwrap_or(CHAT_PATH),
+        )?;
+
+        let json_value = self
+            .auth_api_client
+            .post_request(
+                url,
+                &json!(ChatRequest {
+                    model: model.to_string(),
+                    messages: messages
+                        .iter()
+                        .map(OllamaMessage::try_from)
+                        .collect::<Result<Vec<OllamaMessage>, WebApiClientError>>()?,
+                    tools: tools.iter().map(Tool::to_function_definition).collect(),
+                    stream: false,
+                    format,
+                    keep_alive: Some("10m".to_string()),
+                }),
+            )
+            .await?;
+
+        let parsed: ChatResponse = match serde_json::from_value(json_value) {
+            Ok(response) => response,
             Err(e) => {
-                return Err(WebApiClientError::InvalidInput(format!("Invalid URL: {e}")));
+                return Err(WebApiClientError::ParseError(format!(
+                    "Failed to parse chat response: {e}"
+                )));
             }
         };
 
-        let json_value = self
+        Ok(parsed)
+    }
+
+    /// Streams partial responses as Ollama produces them. Only the final chunk
+    /// (`done == true`) carries the context, timings and token counts.
+    pub async fn generate_stream(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+        json: bool,
+    ) -> Result<BoxStream<'static, Result<GenerateResponse, WebApiClientError>>, WebApiClientError>
+    {
+        let format = if json { Some(json!("json")) } else { None };
+
+        let url = join_url(
+            &self.base_url,
+            self.paths.generate.as_deref().unwrap_or(GENERATE_PATH),
+        )?;
+
+        let bytes = self
             .auth_api_client
-            .post_request(
+            .post_stream(
                 url,
-                &json!(EmbeddingRequest {
+                &json!(GenerateRequest {
                     model: model.to_string(),
-                    prompt: text.to_string(),
+                    system: Some(system_prompt.to_string()),
+                    prompt: prompt.to_string(),
+                    stream: true,
+                    format,
+                    ..Default::default()
                 }),
             )
             .await?;
 
-        let parsed: EmbeddingResponse = match serde_json::from_value(json_value) {
+        Ok(json_lines::<Value>(bytes)
+            .map(|chunk| {
+                let chunk = chunk?;
+                if let Some(error) = chunk.get("error") {
+                    let error = error
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or(error.to_string());
+                    return Err(WebApiClientError::PostFailed(format!(
+                        "Ollama stream error: {error}"
+                    )));
+                }
+
+                serde_json::from_value(chunk).map_err(|e| {
+                    WebApiClientError::ParseError(format!(
+                        "Failed to parse generate stream chunk: {e}"
+                    ))
+                })
+            })
+            .boxed())
+    }
+
+    /// Embeds many inputs through the batch `/api/embed` endpoint.
+    pub async fn embeddings(
+        &self,
+        model: &str,
+        input: &[String],
+        options: &EmbedOptions,
+    ) -> Result<EmbedResponse, WebApiClientError> {
+        let url = join_url(
+            &self.base_url,
+            self.paths.embeddings.as_deref().unwrap_or(EMBED_PATH),
+        )?;
+
+        let mut response = EmbedResponse {
+            model: model.to_string(),
+            ..Default::default()
+        };
+
+        for batch in input.chunks(options.batch_size()) {
+            let json_value = self
+                .auth_api_client
+                .post_request(
+                    url.clone(),
+                    &json!(EmbeddingRequest {
+                        model: model.to_string(),
+                        input: batch.to_vec(),
+                        dimensions: options.dimensions,
+                        keep_alive: Some("10m".to_string()),
+                    }),
+                )
+                .await?;
+
+            let parsed: EmbeddingResponse = match serde_json::from_value(json_value) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    return Err(WebApiClientError::ParseError(format!(
+                        "Failed to parse embed response: {e}"
+                    )));
+                }
+            };
+
+            if parsed.embeddings.len() != batch.len() {
+                return Err(WebApiClientError::ParseError(format!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    parsed.embeddings.len()
+                )));
+            }
+
+            response.extend(EmbedResponse {
+                model: parsed.model,
+                embeddings: parsed.embeddings,
+                usage: Some(Usage {
+                    prompt_tokens: parsed.prompt_eval_count,
+                    completion_tokens: None,
+                    total_tokens: parsed.prompt_eval_count,
+                }),
+            });
+        }
+
+        if options.normalize {
+            response.normalize();
+        }
+
+        Ok(response)
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, WebApiClientError> {
+        let url = join_url(
+            &self.base_url,
+            self.paths.models.as_deref().unwrap_or(TAGS_PATH),
+        )?;
+
+        let json_value = self.auth_api_client.get_request(url).await?;
+
+        let parsed: TagsResponse = match serde_json::from_value(json_value) {
             Ok(parsed) => parsed,
             Err(e) => {
                 return Err(WebApiClientError::ParseError(format!(
-                    "Failed to parse response: {e}"
+                    "Failed to parse tags response: {e}"
                 )));
             }
         };
 
-        Ok(parsed)
+        Ok(parsed
+            .models
+            .into_iter()
+            .map(|model| ModelInfo {
+                name: model.name,
+                owned_by: None,
+                size: model.size,
+            })
+            .collect())
+    }
+}
+
+impl From<GenerateResponse> for LlmResponse {
+    fn from(response: GenerateResponse) -> Self {
+        LlmResponse {
+            model: response.model,
+            content: response.response,
+            finish_reason: response.done_reason,
+            usage: Some(Usage::new(response.prompt_eval_count, response.eval_count)),
+            tool_calls: Vec::new(),
+        }
+    }
+}
+
+impl From<ChatResponse> for LlmResponse {
+    fn from(response: ChatResponse) -> Self {
+        LlmResponse {
+            model: response.model,
+            content: response.message.text(),
+            finish_reason: response.done_reason,
+            usage: Some(Usage::new(response.prompt_eval_count, response.eval_count)),
+            tool_calls: response.message.tool_calls,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmClient for OllamaClient {
+    async fn generate(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = OllamaClient::generate(self, model, system_prompt, prompt, json).await?;
+        Ok(response.into())
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = OllamaClient::chat(self, model, messages, json).await?;
+        Ok(response.into())
+    }
+
+    async fn chat_with_tools(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = OllamaClient::chat_with_tools(self, model, messages, tools).await?;
+        Ok(response.into())
+    }
+
+    async fn chat_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let response = OllamaClient::chat_structured(self, model, messages, schema).await?;
+        Ok(response.into())
+    }
+
+    async fn embed(
+        &self,
+        model: &str,
+        input: &[String],
+        options: &EmbedOptions,
+    ) -> Result<EmbedResponse, LlmClientError> {
+        Ok(self.embeddings(model, input, options).await?)
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmClientError> {
+        Ok(OllamaClient::list_models(self).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wire(message: &ChatMessage) -> Value {
+        serde_json::to_value(OllamaMessage::try_from(message).unwrap()).unwrap()
+    }
+
+    fn round_trip(message: &ChatMessage) -> ChatMessage {
+        serde_json::from_value::<OllamaMessage>(wire(message))
+            .unwrap()
+            .into()
+    }
+
+    fn call(id: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: "weather".to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn sends_text_content() {
+        let message = ChatMessage::user("Hello");
+
+        assert_eq!(
+            wire(&message),
+            json!({ "role": "user", "content": "Hello" })
+        );
+        assert_eq!(round_trip(&message), message);
+    }
+
+    #[test]
+    fn sends_base64_images_alongside_text() {
+        let message =
+            ChatMessage::user("What is this?").with_image_base64("image/png", "iVBORw0KGgo=");
+
+        assert_eq!(
+            wire(&message),
+            json!({ "role": "user", "content": "What is this?", "images": ["iVBORw0KGgo="] })
+        );
+        assert_eq!(round_trip(&message), message);
+    }
+
+    #[test]
+    fn rejects_image_urls() {
+        let message =
+            ChatMessage::user("What is this?").with_image_url("https://example.com/cat.jpg");
+
+        assert!(matches!(
+            OllamaMessage::try_from(&message),
+            Err(WebApiClientError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn sends_tool_arguments_as_object() {
+        let message = ChatMessage::assistant("").with_tool_calls(vec![
+            call("call-1", json!({ "city": "Oslo" })),
+            call("call-2", json!(r#"{"city": "Rome"}"#)),
+        ]);
+
+        assert_eq!(
+            wire(&message),
+            json!({
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    { "function": { "name": "weather", "arguments": { "city": "Oslo" } } },
+                    { "function": { "name": "weather", "arguments": { "city": "Rome" } } },
+                ],
+            })
+        );
+
+        // Ollama sends no ids, so fresh ones are made up
+        let tool_calls = round_trip(&message).tool_calls;
+        assert_eq!(tool_calls.len(), 2);
+        assert_eq!(tool_calls[0].arguments, json!({ "city": "Oslo" }));
+        assert!(tool_calls[0].id.starts_with("call_"));
+        assert_ne!(tool_calls[0].id, tool_calls[1].id);
+    }
+
+    #[test]
+    fn sends_tool_results_by_tool_name() {
+        let message = ChatMessage::tool("call-1", "rain").with_name("weather");
+
+        assert_eq!(
+            wire(&message),
+            json!({ "role": "tool", "content": "rain", "tool_name": "weather" })
+        );
+
+        let message = ChatMessage::user("Hi").with_name("alice");
+        assert_eq!(wire(&message), json!({ "role": "user", "content": "Hi" }));
     }
 }
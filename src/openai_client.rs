@@ -1,9 +1,19 @@
-use crate::settings::ServerConfig;
-use crate::web_api_client::WebApiClient;
-use log::debug;
+use crate::chat_message::{ContentPart, Role, ToolCall};
+use crate::concurrency_limiter::ConcurrencyLimiter;
+use crate::llm_client::{
+    EmbedOptions, EmbedResponse, LlmClient, LlmClientError, LlmResponse, ModelInfo, Usage,
+};
+use crate::settings::{AzureConfig, EndpointPaths, ServerConfig};
+use crate::structured::{schema_name, strict_schema};
+use crate::tool::Tool;
+use crate::web_api_client::{WebApiClient, WebApiClientError, join_url};
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::{Value, json};
 use std::fmt::Display;
+use std::sync::Arc;
 use url::Url;
 
 #[derive(Debug)]
@@ -11,6 +21,7 @@ pub enum OpenAiClientError {
     InvalidApiKey(String),
     InvalidInput(String),
     CompletionFailed(String),
+    RequestFailed(WebApiClientError),
 }
 
 impl Display for OpenAiClientError {
@@ -19,11 +30,23 @@ impl Display for OpenAiClientError {
             OpenAiClientError::InvalidApiKey(msg) => write!(f, "Invalid API Key: {}", msg),
             OpenAiClientError::InvalidInput(msg) => write!(f, "Invalid Input: {}", msg),
             OpenAiClientError::CompletionFailed(msg) => write!(f, "Completion Failed: {}", msg),
+            OpenAiClientError::RequestFailed(_) => write!(f, "Request Failed"),
         }
     }
 }
 
-#[derive(Serialize, Debug)]
+impl std::error::Error for OpenAiClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OpenAiClientError::RequestFailed(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+pub use crate::chat_message::ChatMessage;
+
+#[derive(Serialize, Debug, Default)]
 pub struct NewChatCompletion {
     model: String,
     system: Option<String>,
@@ -31,78 +54,329 @@ pub struct NewChatCompletion {
     format: Option<String>,
 }
 
-impl Default for NewChatCompletion {
-    fn default() -> Self {
-        Self {
-            model: String::new(),
-            system: None,
-            prompt: String::new(),
-            format: None,
+/// A chat message in the OpenAI wire format, where `content` is either a
+/// string or a list of typed parts and tool arguments are a JSON string.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenAiMessage {
+    pub role: Role,
+    #[serde(default)]
+    pub content: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<OpenAiToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenAiToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    pub function: OpenAiFunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenAiFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: String,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+fn json_response_format(json: bool) -> Option<Value> {
+    if json {
+        Some(json!({"type": "json_object"}))
+    } else {
+        None
+    }
+}
+
+impl From<&ChatMessage> for OpenAiMessage {
+    fn from(message: &ChatMessage) -> Self {
+        let content = match message.content.as_slice() {
+            [] => None,
+            [ContentPart::Text { text }] => Some(Value::String(text.clone())),
+            parts => Some(Value::Array(
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        ContentPart::Text { text } => json!({"type": "text", "text": text}),
+                        ContentPart::Image { source } => {
+                            json!({"type": "image_url", "image_url": {"url": source.to_url()}})
+                        }
+                    })
+                    .collect(),
+            )),
+        };
+
+        // tool results are matched by tool_call_id, OpenAI rejects a name on them
+        let name = match message.role {
+            Role::Tool => None,
+            _ => message.name.clone(),
+        };
+
+        OpenAiMessage {
+            role: message.role,
+            content,
+            name,
+            tool_calls: message
+                .tool_calls
+                .iter()
+                .map(|call| OpenAiToolCall {
+                    id: call.id.clone(),
+                    kind: function_type(),
+                    function: OpenAiFunctionCall {
+                        name: call.name.clone(),
+                        // arguments that were not valid JSON are kept as received
+                        arguments: match &call.arguments {
+                            Value::String(arguments) => arguments.clone(),
+                            arguments => arguments.to_string(),
+                        },
+                    },
+                })
+                .collect(),
+            tool_call_id: message.tool_call_id.clone(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ChatMessage {
-    role: String,
-    content: String,
+impl From<OpenAiMessage> for ChatMessage {
+    fn from(message: OpenAiMessage) -> Self {
+        let mut chat_message = match message.content {
+            Some(Value::String(text)) => ChatMessage::new(message.role, &text),
+            Some(Value::Array(parts)) => {
+                let text: String = parts
+                    .iter()
+                    .filter_map(|part| part.get("text").and_then(Value::as_str))
+                    .collect();
+                ChatMessage::new(message.role, &text)
+            }
+            _ => ChatMessage::new(message.role, ""),
+        };
+
+        chat_message.name = message.name;
+        chat_message.tool_call_id = message.tool_call_id;
+        chat_message.tool_calls = message
+            .tool_calls
+            .into_iter()
+            .map(|call| ToolCall {
+                id: call.id,
+                // arguments are sent as a JSON string, which models occasionally get wrong
+                arguments: serde_json::from_str(&call.function.arguments)
+                    .unwrap_or(Value::String(call.function.arguments)),
+                name: call.function.name,
+            })
+            .collect();
+
+        chat_message
+    }
+}
+
+fn deserialize_message<'de, D>(deserializer: D) -> Result<ChatMessage, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    OpenAiMessage::deserialize(deserializer).map(ChatMessage::from)
 }
 
 #[derive(Serialize, Debug)]
 pub struct ChatCompletionRequest {
     model: String,
-    messages: Vec<ChatMessage>,
+    messages: Vec<OpenAiMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ChatCompletionChoice {
     pub index: i64,
+    #[serde(deserialize_with = "deserialize_message")]
     pub message: ChatMessage,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionUsage {
+    pub prompt_tokens: Option<usize>,
+    pub completion_tokens: Option<usize>,
+    pub total_tokens: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ChatCompletionResponse {
-    choices: Vec<ChatCompletionChoice>,
+    #[serde(default)]
+    pub model: String,
+    pub choices: Vec<ChatCompletionChoice>,
+    pub usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ChatCompletionDelta {
+    pub role: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChatCompletionChunkChoice {
+    pub index: i64,
+    #[serde(default)]
+    pub delta: ChatCompletionDelta,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChatCompletionChunk {
+    #[serde(default)]
+    pub model: String,
+    pub choices: Vec<ChatCompletionChunkChoice>,
+}
+
+/// Rebuilds the assistant message from streamed chunks.
+#[derive(Debug, Default, Clone)]
+pub struct ChatCompletionAccumulator {
+    role: Option<Role>,
+    content: String,
+    finish_reason: Option<String>,
+}
+
+impl ChatCompletionAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &ChatCompletionChunk) {
+        for choice in chunk.choices.iter().filter(|c| c.index == 0) {
+            if let Some(role) = &choice.delta.role {
+                self.role = Role::try_from(role.as_str()).ok();
+            }
+            if let Some(content) = &choice.delta.content {
+                self.content.push_str(content);
+            }
+            if choice.finish_reason.is_some() {
+                self.finish_reason = choice.finish_reason.clone();
+            }
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn finish_reason(&self) -> Option<&str> {
+        self.finish_reason.as_deref()
+    }
+
+    pub fn message(&self) -> ChatMessage {
+        ChatMessage::new(self.role.unwrap_or(Role::Assistant), &self.content)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct EmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingsResponse {
+    #[serde(default)]
+    model: String,
+    data: Vec<EmbeddingsData>,
+    usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingsData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsResponse {
+    data: Vec<ModelsData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsData {
+    id: String,
+    owned_by: Option<String>,
+}
+
+impl From<ChatCompletionUsage> for Usage {
+    fn from(usage: ChatCompletionUsage) -> Self {
+        Usage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
 }
 
 pub struct OpenAiClient {
     auth_api_client: WebApiClient,
     base_url: Url,
+    azure: Option<AzureConfig>,
+    paths: EndpointPaths,
 }
 
 impl OpenAiClient {
+    /// Clients given the same `concurrency_limiter` share its limit.
     pub fn new(
         setting: &ServerConfig,
         api_key: Option<&String>,
+        concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
     ) -> Result<Self, OpenAiClientError> {
         // check if the API key is empty
         if api_key.unwrap_or(&String::new()).is_empty() {
-            return Err(OpenAiClientError::InvalidApiKey(format!(
-                "API key cannot be empty"
-            )));
+            return Err(OpenAiClientError::InvalidApiKey(
+                "API key cannot be empty".to_string(),
+            ));
         }
         let api_key = api_key.unwrap();
 
-        debug!(
-            "Setting Connection Timeout: {}",
-            setting.connection_timeout.unwrap_or(u64::MAX)
-        );
+        let mut auth_api_client = WebApiClient::for_server(setting, concurrency_limiter);
 
-        debug!(
-            "Setting Deadline Timeout: {}",
-            setting.deadline_timeout.unwrap_or(u64::MAX)
-        );
+        let is_azure = setting.api_type.eq_ignore_ascii_case("azure");
+        if is_azure && setting.azure.is_none() {
+            return Err(OpenAiClientError::InvalidInput(format!(
+                "Server {} has api_type azure but no azure settings",
+                setting.name
+            )));
+        }
 
-        let mut auth_api_client =
-            WebApiClient::new(setting.connection_timeout, setting.deadline_timeout);
+        if let Some(azure) = &setting.azure
+            && azure.deployment.trim().is_empty()
+        {
+            return Err(OpenAiClientError::InvalidInput(format!(
+                "Server {} has no azure deployment",
+                setting.name
+            )));
+        }
 
-        match auth_api_client.add_header("Authorization", format!("Bearer {}", api_key)) {
+        // Azure takes the key as is, OpenAI as a bearer token
+        let header = match setting.azure {
+            Some(_) => auth_api_client.add_header("api-key", api_key.clone()),
+            None => auth_api_client.add_header("Authorization", format!("Bearer {}", api_key)),
+        };
+
+        match header {
             Ok(client) => client,
             Err(e) => {
                 return Err(OpenAiClientError::InvalidApiKey(format!(
                     "Failed to add header to WebApiClient: {}",
                     e
-                )))
+                )));
             }
         };
 
@@ -112,20 +386,46 @@ impl OpenAiClient {
                 return Err(OpenAiClientError::InvalidInput(format!(
                     "Failed to parse base API URL ({}): {}",
                     setting.base_api_url, e
-                )))
+                )));
             }
         };
 
         Ok(Self {
             auth_api_client,
             base_url,
+            azure: setting.azure.clone(),
+            paths: setting.paths.clone(),
         })
     }
+
+    /// The URL of an API operation such as `chat/completions`, below the base
+    /// URL's path. `path` overrides the standard `v1/{operation}`. Azure routes
+    /// the operation through the deployment and adds the `api-version` parameter.
+    fn url(&self, operation: &str, path: Option<&str>) -> Result<Url, OpenAiClientError> {
+        let path = match (&self.azure, path) {
+            (Some(_), _) if operation == "models" => "openai/models".to_string(),
+            (Some(azure), _) => format!("openai/deployments/{}/{operation}", azure.deployment),
+            (None, Some(path)) => path.to_string(),
+            (None, None) => format!("v1/{operation}"),
+        };
+
+        let mut url = match join_url(&self.base_url, &path) {
+            Ok(url) => url,
+            Err(e) => return Err(OpenAiClientError::InvalidInput(e.to_string())),
+        };
+
+        if let Some(azure) = &self.azure {
+            url.query_pairs_mut()
+                .append_pair("api-version", &azure.api_version);
+        }
+
+        Ok(url)
+    }
     pub async fn generate(
         &self,
-        model: &String,
-        system_prompt: &String,
-        prompt: &String,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
         json: bool,
     ) -> Result<String, OpenAiClientError> {
         self.chat_completion(model, system_prompt, prompt, json)
@@ -134,73 +434,497 @@ impl OpenAiClient {
 
     pub async fn chat_completion(
         &self,
-        model: &String,
-        system_prompt: &String,
-        prompt: &String,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
         json: bool,
     ) -> Result<String, OpenAiClientError> {
-        let _format = if json { Some("json".to_string()) } else { None };
+        let messages = vec![
+            ChatMessage::system(system_prompt),
+            ChatMessage::user(prompt),
+        ];
 
-        let url = match self.base_url.join("/v1/chat/completions") {
-            Ok(url) => url,
-            Err(e) => {
-                return Err(OpenAiClientError::InvalidInput(format!(
-                    "Invalid URL: {}",
-                    e
-                )))
-            }
-        };
+        let parsed = self
+            .send_chat_completion(model, &messages, &[], json_response_format(json))
+            .await?;
+
+        // find the message from "assistant"
+        let response: Option<&ChatCompletionChoice> = parsed
+            .choices
+            .iter()
+            .find(|o| o.message.role == Role::Assistant);
+
+        match response {
+            Some(choice) => Ok(choice.message.text()),
+            None => Err(OpenAiClientError::CompletionFailed(
+                "No assistant response found".to_string(),
+            )),
+        }
+    }
+
+    async fn send_chat_completion(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+        response_format: Option<Value>,
+    ) -> Result<ChatCompletionResponse, OpenAiClientError> {
+        let url = self.url("chat/completions", self.paths.chat.as_deref())?;
 
         let request = ChatCompletionRequest {
-            model: model.clone(),
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: system_prompt.clone(),
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: prompt.clone(),
-                },
-            ],
+            model: model.to_string(),
+            messages: messages.iter().map(OpenAiMessage::from).collect(),
+            tools: tools.iter().map(Tool::to_function_definition).collect(),
+            response_format,
+            stream: None,
         };
 
-        let json_value: &Value = match self
+        let json_value: Value = match self
             .auth_api_client
             .post_request(url, &json!(request))
             .await
         {
-            Ok(json_value) => &json_value.to_owned(),
-            Err(e) => {
+            Ok(json_value) => json_value,
+            Err(e) => return Err(OpenAiClientError::RequestFailed(e)),
+        };
+
+        match serde_json::from_value(json_value) {
+            Ok(response) => Ok(response),
+            Err(e) => Err(OpenAiClientError::CompletionFailed(format!(
+                "Failed to parse chat_completion response: {}",
+                e
+            ))),
+        }
+    }
+
+    /// Streams the completion as chunks of content deltas. The stream ends at
+    /// the `[DONE]` sentinel; the chunk carrying `finish_reason` comes just before it.
+    pub async fn chat_completion_stream(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+    ) -> Result<BoxStream<'static, Result<ChatCompletionChunk, OpenAiClientError>>, OpenAiClientError>
+    {
+        let url = self.url("chat/completions", self.paths.chat.as_deref())?;
+
+        let request = ChatCompletionRequest {
+            model: model.to_string(),
+            messages: messages.iter().map(OpenAiMessage::from).collect(),
+            tools: Vec::new(),
+            response_format: None,
+            stream: Some(true),
+        };
+
+        let events = match self
+            .auth_api_client
+            .post_event_stream(url, &json!(request))
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => return Err(OpenAiClientError::RequestFailed(e)),
+        };
+
+        Ok(events
+            .take_while(|event| {
+                let done = matches!(event, Ok(event) if event.data.trim() == "[DONE]");
+                async move { !done }
+            })
+            .map(|event| {
+                let event = event.map_err(OpenAiClientError::RequestFailed)?;
+
+                serde_json::from_str(&event.data).map_err(|e| {
+                    OpenAiClientError::CompletionFailed(format!(
+                        "Failed to parse chat_completion chunk: {}",
+                        e
+                    ))
+                })
+            })
+            .boxed())
+    }
+
+    pub async fn embeddings(
+        &self,
+        model: &str,
+        input: &[String],
+        options: &EmbedOptions,
+    ) -> Result<EmbedResponse, OpenAiClientError> {
+        let url = self.url("embeddings", self.paths.embeddings.as_deref())?;
+
+        let mut response = EmbedResponse {
+            model: model.to_string(),
+            ..Default::default()
+        };
+
+        for batch in input.chunks(options.batch_size()) {
+            let request = EmbeddingsRequest {
+                model,
+                input: batch,
+                dimensions: options.dimensions,
+            };
+
+            let json_value = match self
+                .auth_api_client
+                .post_request(url.clone(), &json!(request))
+                .await
+            {
+                Ok(json_value) => json_value,
+                Err(e) => return Err(OpenAiClientError::RequestFailed(e)),
+            };
+
+            let mut parsed: EmbeddingsResponse = match serde_json::from_value(json_value) {
+                Ok(response) => response,
+                Err(e) => {
+                    return Err(OpenAiClientError::CompletionFailed(format!(
+                        "Failed to parse embeddings response: {}",
+                        e
+                    )));
+                }
+            };
+
+            if parsed.data.len() != batch.len() {
                 return Err(OpenAiClientError::CompletionFailed(format!(
-                    "POST request failed: {}",
-                    e
-                )))
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    parsed.data.len()
+                )));
             }
+
+            parsed.data.sort_by_key(|d| d.index);
+
+            response.extend(EmbedResponse {
+                model: parsed.model,
+                embeddings: parsed.data.into_iter().map(|d| d.embedding).collect(),
+                usage: parsed.usage.map(Usage::from),
+            });
+        }
+
+        if options.normalize {
+            response.normalize();
+        }
+
+        Ok(response)
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, OpenAiClientError> {
+        let url = self.url("models", self.paths.models.as_deref())?;
+
+        let json_value = match self.auth_api_client.get_request(url).await {
+            Ok(json_value) => json_value,
+            Err(e) => return Err(OpenAiClientError::RequestFailed(e)),
         };
 
-        let parsed: ChatCompletionResponse = match serde_json::from_value(json_value.clone()) {
+        let parsed: ModelsResponse = match serde_json::from_value(json_value) {
             Ok(response) => response,
             Err(e) => {
                 return Err(OpenAiClientError::CompletionFailed(format!(
-                    "Failed to parse chat_completion response: {}",
+                    "Failed to parse models response: {}",
                     e
-                )))
+                )));
             }
         };
 
-        // find the message from "assistant"
-        let response: Option<&ChatCompletionChoice> = parsed
-            .choices
-            .iter()
-            .find(|o| o.message.role == "assistant");
+        Ok(parsed
+            .data
+            .into_iter()
+            .map(|model| ModelInfo {
+                name: model.id,
+                owned_by: model.owned_by,
+                size: None,
+            })
+            .collect())
+    }
+}
 
-        if response.is_none() {
-            return Err(OpenAiClientError::CompletionFailed(
+#[async_trait]
+impl LlmClient for OpenAiClient {
+    async fn generate(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let messages = [
+            ChatMessage::system(system_prompt),
+            ChatMessage::user(prompt),
+        ];
+
+        self.chat(model, &messages, json).await
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        json: bool,
+    ) -> Result<LlmResponse, LlmClientError> {
+        let parsed = self
+            .send_chat_completion(model, messages, &[], json_response_format(json))
+            .await?;
+        completion_to_response(parsed)
+    }
+
+    async fn chat_with_tools(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[Tool],
+    ) -> Result<LlmResponse, LlmClientError> {
+        let parsed = self
+            .send_chat_completion(model, messages, tools, None)
+            .await?;
+        completion_to_response(parsed)
+    }
+
+    async fn chat_structured(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        schema: &Value,
+    ) -> Result<LlmResponse, LlmClientError> {
+        // strict mode is what makes OpenAI follow the schema, and it only
+        // accepts an object at the root
+        let strict = schema.get("type").and_then(Value::as_str) == Some("object");
+        let response_format = json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": schema_name(schema),
+                "schema": if strict { strict_schema(schema) } else { schema.clone() },
+                "strict": strict,
+            }
+        });
+
+        let parsed = self
+            .send_chat_completion(model, messages, &[], Some(response_format))
+            .await?;
+        completion_to_response(parsed)
+    }
+
+    async fn embed(
+        &self,
+        model: &str,
+        input: &[String],
+        options: &EmbedOptions,
+    ) -> Result<EmbedResponse, LlmClientError> {
+        Ok(self.embeddings(model, input, options).await?)
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmClientError> {
+        Ok(OpenAiClient::list_models(self).await?)
+    }
+}
+
+fn completion_to_response(parsed: ChatCompletionResponse) -> Result<LlmResponse, LlmClientError> {
+    let choice = match parsed
+        .choices
+        .into_iter()
+        .find(|o| o.message.role == Role::Assistant)
+    {
+        Some(choice) => choice,
+        None => {
+            return Err(LlmClientError::RequestFailed(
                 "No assistant response found".to_string(),
             ));
         }
+    };
+
+    Ok(LlmResponse {
+        model: parsed.model,
+        content: choice.message.text(),
+        finish_reason: choice.finish_reason,
+        usage: parsed.usage.map(Usage::from),
+        tool_calls: choice.message.tool_calls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(config: &str) -> ServerConfig {
+        toml::from_str(config).unwrap()
+    }
+
+    fn azure_server() -> ServerConfig {
+        server(
+            r#"
+            name = "azure"
+            model = "gpt-4o"
+            api_type = "azure"
+            base_api_url = "https://tenant.openai.azure.com"
+            azure = { deployment = "prod-gpt4o", api_version = "2024-06-01" }
+            "#,
+        )
+    }
+
+    fn key() -> Option<String> {
+        Some("secret-key".to_string())
+    }
+
+    fn wire(message: &ChatMessage) -> Value {
+        serde_json::to_value(OpenAiMessage::from(message)).unwrap()
+    }
+
+    fn round_trip(message: &ChatMessage) -> ChatMessage {
+        serde_json::from_value::<OpenAiMessage>(wire(message))
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn sends_plain_text_as_string_content() {
+        let message = ChatMessage::user("Hello");
+
+        assert_eq!(
+            wire(&message),
+            json!({ "role": "user", "content": "Hello" })
+        );
+        assert_eq!(round_trip(&message), message);
+    }
+
+    #[test]
+    fn sends_multipart_content_with_data_url_images() {
+        let message = ChatMessage::user("What is this?")
+            .with_image_base64("image/png", "iVBORw0KGgo=")
+            .with_image_url("https://example.com/cat.jpg");
+
+        assert_eq!(
+            wire(&message),
+            json!({
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "What is this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } },
+                    { "type": "image_url", "image_url": { "url": "https://example.com/cat.jpg" } },
+                ],
+            })
+        );
+        // images in replies are not read back, only their text
+        assert_eq!(round_trip(&message).text(), "What is this?");
+    }
+
+    #[test]
+    fn sends_tool_arguments_as_json_string() {
+        let message = ChatMessage::assistant("").with_tool_calls(vec![
+            ToolCall {
+                id: "call-1".to_string(),
+                name: "weather".to_string(),
+                arguments: json!({ "city": "Oslo" }),
+            },
+            ToolCall {
+                id: "call-2".to_string(),
+                name: "weather".to_string(),
+                arguments: json!("{\"city\": "),
+            },
+        ]);
+
+        let json = wire(&message);
+        assert_eq!(json["tool_calls"][0]["type"], "function");
+        assert_eq!(
+            json["tool_calls"][0]["function"]["arguments"],
+            r#"{"city":"Oslo"}"#
+        );
+        assert_eq!(
+            json["tool_calls"][1]["function"]["arguments"],
+            r#"{"city": "#
+        );
+
+        let tool_calls = round_trip(&message).tool_calls;
+        assert_eq!(tool_calls, message.tool_calls);
+    }
+
+    #[test]
+    fn sends_tool_results_by_tool_call_id() {
+        let message = ChatMessage::tool("call-1", "rain").with_name("weather");
+
+        assert_eq!(
+            wire(&message),
+            json!({ "role": "tool", "content": "rain", "tool_call_id": "call-1" })
+        );
+
+        let read = round_trip(&message);
+        assert_eq!(read.tool_call_id.as_deref(), Some("call-1"));
+        assert_eq!(read.text(), "rain");
+    }
+
+    #[test]
+    fn azure_urls_route_through_the_deployment() {
+        let client = OpenAiClient::new(&azure_server(), key().as_ref(), None).unwrap();
+
+        assert_eq!(
+            client.url("chat/completions", None).unwrap().as_str(),
+            "https://tenant.openai.azure.com/openai/deployments/prod-gpt4o/chat/completions?api-version=2024-06-01"
+        );
+        assert_eq!(
+            client.url("embeddings", None).unwrap().as_str(),
+            "https://tenant.openai.azure.com/openai/deployments/prod-gpt4o/embeddings?api-version=2024-06-01"
+        );
+        assert_eq!(
+            client.url("models", None).unwrap().as_str(),
+            "https://tenant.openai.azure.com/openai/models?api-version=2024-06-01"
+        );
+    }
+
+    #[test]
+    fn azure_sends_the_key_as_api_key_header() {
+        let client = OpenAiClient::new(&azure_server(), key().as_ref(), None).unwrap();
+        let headers = client.auth_api_client.headers();
+
+        assert_eq!(headers.get("api-key").unwrap(), "secret-key");
+        assert!(headers.get("authorization").is_none());
+    }
+
+    #[test]
+    fn openai_uses_bearer_token_and_v1_paths() {
+        let setting = server(
+            r#"
+            name = "openai"
+            model = "gpt-4o"
+            api_type = "openai"
+            base_api_url = "https://api.openai.com"
+            "#,
+        );
+        let client = OpenAiClient::new(&setting, key().as_ref(), None).unwrap();
+
+        assert_eq!(
+            client.url("chat/completions", None).unwrap().as_str(),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(
+            client
+                .url("chat/completions", Some("/openai/chat"))
+                .unwrap()
+                .as_str(),
+            "https://api.openai.com/openai/chat"
+        );
+
+        let headers = client.auth_api_client.headers();
+        assert_eq!(headers.get("authorization").unwrap(), "Bearer secret-key");
+        assert!(headers.get("api-key").is_none());
+    }
+
+    #[test]
+    fn azure_without_deployment_is_rejected() {
+        let mut setting = azure_server();
+        setting.azure.as_mut().unwrap().deployment = " ".to_string();
+        assert!(matches!(
+            OpenAiClient::new(&setting, key().as_ref(), None),
+            Err(OpenAiClientError::InvalidInput(_))
+        ));
+
+        setting.azure = None;
+        assert!(matches!(
+            OpenAiClient::new(&setting, key().as_ref(), None),
+            Err(OpenAiClientError::InvalidInput(_))
+        ));
 
-        Ok(response.unwrap().message.content.clone())
+        let config = r#"
+            name = "azure"
+            model = "gpt-4o"
+            api_type = "azure"
+            base_api_url = "https://tenant.openai.azure.com"
+            azure = { api_version = "2024-06-01" }
+            "#;
+        assert!(toml::from_str::<ServerConfig>(config).is_err());
     }
 }
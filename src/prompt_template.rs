@@ -0,0 +1,678 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+// guards against partials that include themselves
+const MAX_PARTIAL_DEPTH: usize = 32;
+
+#[derive(Debug)]
+pub enum PromptTemplateError {
+    SyntaxError(String),
+    MissingVariable(String),
+    MissingPartial(String),
+    InvalidMode(String),
+    ReadError(String),
+}
+
+impl Display for PromptTemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PromptTemplateError::SyntaxError(msg) => write!(f, "Template syntax error: {msg}"),
+            PromptTemplateError::MissingVariable(name) => {
+                write!(f, "Missing template variable `{name}`")
+            }
+            PromptTemplateError::MissingPartial(name) => {
+                write!(f, "Missing template partial `{name}`")
+            }
+            PromptTemplateError::InvalidMode(mode) => write!(f, "Invalid template mode `{mode}`"),
+            PromptTemplateError::ReadError(msg) => write!(f, "Unable to read template: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for PromptTemplateError {}
+
+/// How an endpoint's `template` setting asks for its prompts to be rendered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemplateMode {
+    /// Missing variables are errors. Used when `template` is empty.
+    Strict,
+    /// Missing variables render as empty text.
+    Lenient,
+    /// Prompts are sent exactly as written.
+    Raw,
+}
+
+impl TryFrom<&str> for TemplateMode {
+    type Error = PromptTemplateError;
+
+    fn try_from(mode: &str) -> Result<Self, Self::Error> {
+        match mode.to_lowercase().as_str() {
+            "" | "strict" => Ok(TemplateMode::Strict),
+            "lenient" => Ok(TemplateMode::Lenient),
+            "raw" | "none" => Ok(TemplateMode::Raw),
+            _ => Err(PromptTemplateError::InvalidMode(mode.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Variable(String),
+    If {
+        path: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Each {
+        path: String,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Partial(String),
+}
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Tag(String),
+}
+
+/// A parsed prompt using `{{name}}` placeholders, `{{#if}}`/`{{#unless}}`
+/// conditionals, `{{#each}}` loops, `{{> partial}}` includes and `{{! comments }}`.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    nodes: Vec<Node>,
+}
+
+impl PromptTemplate {
+    pub fn parse(source: &str) -> Result<Self, PromptTemplateError> {
+        let tokens = strip_standalone_tags(tokenize(source)?);
+        let mut position = 0;
+        let (nodes, closing) = parse_nodes(&tokens, &mut position)?;
+
+        if let Some(closing) = closing {
+            return Err(PromptTemplateError::SyntaxError(format!(
+                "Unexpected `{{{{{closing}}}}}`"
+            )));
+        }
+
+        Ok(Self { nodes })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PromptRenderer {
+    partials: HashMap<String, PromptTemplate>,
+}
+
+impl PromptRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_partial(&mut self, name: &str, source: &str) -> Result<(), PromptTemplateError> {
+        self.partials
+            .insert(name.to_string(), PromptTemplate::parse(source)?);
+        Ok(())
+    }
+
+    /// Registers every file in `dir` as a partial named after its file stem.
+    pub fn load_partials(&mut self, dir: &Path) -> Result<(), PromptTemplateError> {
+        let entries = read_dir(dir)
+            .map_err(|e| PromptTemplateError::ReadError(format!("{}: {e}", dir.display())))?;
+
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => return Err(PromptTemplateError::ReadError(e.to_string())),
+            };
+
+            if !path.is_file() {
+                continue;
+            }
+
+            let name = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            let source = read_to_string(&path)
+                .map_err(|e| PromptTemplateError::ReadError(format!("{}: {e}", path.display())))?;
+
+            self.add_partial(&name, &source)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn render(
+        &self,
+        source: &str,
+        variables: &HashMap<String, Value>,
+        mode: TemplateMode,
+    ) -> Result<String, PromptTemplateError> {
+        if mode == TemplateMode::Raw {
+            return Ok(source.to_string());
+        }
+
+        let template = PromptTemplate::parse(source)?;
+        self.render_template(&template, variables, mode)
+    }
+
+    pub fn render_template(
+        &self,
+        template: &PromptTemplate,
+        variables: &HashMap<String, Value>,
+        mode: TemplateMode,
+    ) -> Result<String, PromptTemplateError> {
+        let root = Value::Object(
+            variables
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<Map<String, Value>>(),
+        );
+
+        let mut output = String::new();
+        let mut frames = vec![Frame {
+            value: &root,
+            index: None,
+            length: 0,
+        }];
+
+        self.render_nodes(&template.nodes, &mut frames, &mut output, mode, 0)?;
+        Ok(output)
+    }
+
+    fn render_nodes<'a>(
+        &'a self,
+        nodes: &'a [Node],
+        frames: &mut Vec<Frame<'a>>,
+        output: &mut String,
+        mode: TemplateMode,
+        depth: usize,
+    ) -> Result<(), PromptTemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Variable(path) => match lookup(frames, path) {
+                    Some(value) => output.push_str(&value_to_text(&value)),
+                    None if mode == TemplateMode::Strict => {
+                        return Err(PromptTemplateError::MissingVariable(path.clone()));
+                    }
+                    None => {}
+                },
+                Node::If {
+                    path,
+                    negate,
+                    then,
+                    otherwise,
+                } => {
+                    let truthy = lookup(frames, path).is_some_and(|v| is_truthy(&v));
+                    let branch = if truthy != *negate { then } else { otherwise };
+                    self.render_nodes(branch, frames, output, mode, depth)?;
+                }
+                Node::Each {
+                    path,
+                    body,
+                    otherwise,
+                } => {
+                    let items = match resolve(frames, path) {
+                        Some(Value::Array(items)) => items,
+                        Some(Value::Null) | None if mode != TemplateMode::Strict => {
+                            self.render_nodes(otherwise, frames, output, mode, depth)?;
+                            continue;
+                        }
+                        Some(Value::Null) | None => {
+                            return Err(PromptTemplateError::MissingVariable(path.clone()));
+                        }
+                        Some(_) => {
+                            return Err(PromptTemplateError::SyntaxError(format!(
+                                "`{path}` is not a list"
+                            )));
+                        }
+                    };
+
+                    if items.is_empty() {
+                        self.render_nodes(otherwise, frames, output, mode, depth)?;
+                        continue;
+                    }
+
+                    for (index, item) in items.iter().enumerate() {
+                        frames.push(Frame {
+                            value: item,
+                            index: Some(index),
+                            length: items.len(),
+                        });
+                        let result = self.render_nodes(body, frames, output, mode, depth);
+                        frames.pop();
+                        result?;
+                    }
+                }
+                Node::Partial(name) => {
+                    if depth >= MAX_PARTIAL_DEPTH {
+                        return Err(PromptTemplateError::SyntaxError(format!(
+                            "Partial `{name}` is nested too deeply"
+                        )));
+                    }
+
+                    let partial = match self.partials.get(name) {
+                        Some(partial) => partial,
+                        None => return Err(PromptTemplateError::MissingPartial(name.clone())),
+                    };
+
+                    self.render_nodes(&partial.nodes, frames, output, mode, depth + 1)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Frame<'a> {
+    value: &'a Value,
+    index: Option<usize>,
+    length: usize,
+}
+
+// resolves a path to a value borrowed from the render frames
+fn resolve<'a>(frames: &[Frame<'a>], path: &str) -> Option<&'a Value> {
+    let current = frames.last()?;
+
+    if path == "this" || path == "." {
+        return Some(current.value);
+    }
+
+    if let Some(rest) = path.strip_prefix("this.") {
+        return walk(current.value, rest);
+    }
+
+    let first = path.split('.').next()?;
+    frames
+        .iter()
+        .rev()
+        .find(|frame| frame.value.get(first).is_some())
+        .and_then(|frame| walk(frame.value, path))
+}
+
+// like `resolve`, but also knows about the `@index`, `@first` and `@last` loop variables
+fn lookup(frames: &[Frame<'_>], path: &str) -> Option<Value> {
+    let current = frames.last()?;
+
+    match path {
+        "@index" => current.index.map(Value::from),
+        "@first" => current.index.map(|i| Value::Bool(i == 0)),
+        "@last" => current.index.map(|i| Value::Bool(i + 1 == current.length)),
+        _ => resolve(frames, path).cloned(),
+    }
+}
+
+fn walk<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => value.get(key),
+    })
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, PromptTemplateError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_string()));
+        }
+
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                return Err(PromptTemplateError::SyntaxError(format!(
+                    "Unclosed tag: {}",
+                    &rest[start..]
+                )));
+            }
+        };
+
+        let tag = after[..end].trim();
+        if !tag.starts_with('!') {
+            tokens.push(Token::Tag(tag.to_string()));
+        }
+
+        rest = &after[end + 2..];
+    }
+
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string()));
+    }
+
+    Ok(tokens)
+}
+
+fn is_block_tag(tag: &str) -> bool {
+    tag.starts_with('#') || tag.starts_with('/') || tag == "else"
+}
+
+// block tags on a line of their own should not leave blank lines behind
+fn strip_standalone_tags(mut tokens: Vec<Token>) -> Vec<Token> {
+    let standalone: Vec<bool> = (0..tokens.len())
+        .map(|i| is_standalone(&tokens, i))
+        .collect();
+
+    for (i, _) in standalone.iter().enumerate().filter(|(_, s)| **s) {
+        if let Some(Token::Text(text)) = i.checked_sub(1).map(|j| &mut tokens[j]) {
+            let keep = text.rfind('\n').map(|n| n + 1).unwrap_or(0);
+            text.truncate(keep);
+        }
+
+        if let Some(Token::Text(text)) = tokens.get_mut(i + 1) {
+            let skip = text.find('\n').map(|n| n + 1).unwrap_or(text.len());
+            text.replace_range(..skip, "");
+        }
+    }
+
+    tokens
+}
+
+fn is_standalone(tokens: &[Token], i: usize) -> bool {
+    match &tokens[i] {
+        Token::Tag(tag) if is_block_tag(tag) => {}
+        _ => return false,
+    }
+
+    let starts_line = match i.checked_sub(1).map(|j| &tokens[j]) {
+        None => true,
+        Some(Token::Text(text)) => {
+            let line = text.rsplit('\n').next().unwrap_or("");
+            line.trim().is_empty() && (text.contains('\n') || i == 1)
+        }
+        Some(Token::Tag(_)) => false,
+    };
+
+    let ends_line = match tokens.get(i + 1) {
+        None => true,
+        Some(Token::Text(text)) => {
+            let line = text.split('\n').next().unwrap_or("");
+            line.trim().is_empty() && (text.contains('\n') || i + 2 == tokens.len())
+        }
+        Some(Token::Tag(_)) => false,
+    };
+
+    starts_line && ends_line
+}
+
+// parses until the end of input or a closing/else tag, which is returned to the caller
+fn parse_nodes(
+    tokens: &[Token],
+    position: &mut usize,
+) -> Result<(Vec<Node>, Option<String>), PromptTemplateError> {
+    let mut nodes = Vec::new();
+
+    while *position < tokens.len() {
+        let token = &tokens[*position];
+        *position += 1;
+
+        let tag = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text.clone()));
+                continue;
+            }
+            Token::Tag(tag) => tag.as_str(),
+        };
+
+        if tag == "else" || tag.starts_with('/') {
+            return Ok((nodes, Some(tag.to_string())));
+        }
+
+        if let Some(name) = tag.strip_prefix('>') {
+            nodes.push(Node::Partial(name.trim().to_string()));
+            continue;
+        }
+
+        let block = match tag.strip_prefix('#') {
+            Some(block) => block,
+            None => {
+                nodes.push(Node::Variable(tag.to_string()));
+                continue;
+            }
+        };
+
+        let (keyword, path) = match block.split_once(char::is_whitespace) {
+            Some((keyword, path)) => (keyword, path.trim().to_string()),
+            None => {
+                return Err(PromptTemplateError::SyntaxError(format!(
+                    "Block `{{{{{tag}}}}}` is missing a variable"
+                )));
+            }
+        };
+
+        let (body, closing) = parse_nodes(tokens, position)?;
+        let (otherwise, closing) = match closing.as_deref() {
+            Some("else") => parse_nodes(tokens, position)?,
+            _ => (Vec::new(), closing),
+        };
+
+        let expected = format!("/{keyword}");
+        if closing.as_deref() != Some(expected.as_str()) {
+            return Err(PromptTemplateError::SyntaxError(format!(
+                "`{{{{{tag}}}}}` is not closed with `{{{{{expected}}}}}`"
+            )));
+        }
+
+        match keyword {
+            "if" | "unless" => nodes.push(Node::If {
+                path,
+                negate: keyword == "unless",
+                then: body,
+                otherwise,
+            }),
+            "each" => nodes.push(Node::Each {
+                path,
+                body,
+                otherwise,
+            }),
+            _ => {
+                return Err(PromptTemplateError::SyntaxError(format!(
+                    "Unknown block `{keyword}`"
+                )));
+            }
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn variables(value: Value) -> HashMap<String, Value> {
+        match value {
+            Value::Object(map) => map.into_iter().collect(),
+            _ => HashMap::new(),
+        }
+    }
+
+    fn render(source: &str, value: Value) -> Result<String, PromptTemplateError> {
+        PromptRenderer::new().render(source, &variables(value), TemplateMode::Strict)
+    }
+
+    #[test]
+    fn renders_variables() {
+        let output = render(
+            "Hello {{ name }}, you are {{age}} ({{user.city}}). {{! ignored }}{{missing_ok}}",
+            json!({ "name": "Ada", "age": 36, "user": { "city": "London" }, "missing_ok": null }),
+        )
+        .unwrap();
+        assert_eq!(output, "Hello Ada, you are 36 (London). ");
+    }
+
+    #[test]
+    fn missing_variables_depend_on_mode() {
+        let renderer = PromptRenderer::new();
+        let vars = variables(json!({}));
+
+        let strict = renderer.render("Hi {{name}}!", &vars, TemplateMode::Strict);
+        assert!(
+            matches!(strict, Err(PromptTemplateError::MissingVariable(name)) if name == "name")
+        );
+
+        let lenient = renderer.render("Hi {{name}}!", &vars, TemplateMode::Lenient);
+        assert_eq!(lenient.unwrap(), "Hi !");
+
+        let raw = renderer.render("Hi {{name}}!", &vars, TemplateMode::Raw);
+        assert_eq!(raw.unwrap(), "Hi {{name}}!");
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let source = "{{#if admin}}admin{{else}}user{{/if}}/{{#unless admin}}limited{{/unless}}";
+        assert_eq!(render(source, json!({ "admin": true })).unwrap(), "admin/");
+        assert_eq!(
+            render(source, json!({ "admin": false })).unwrap(),
+            "user/limited"
+        );
+        assert_eq!(
+            render(source, json!({ "admin": "" })).unwrap(),
+            "user/limited"
+        );
+        assert_eq!(
+            render(source, json!({ "admin": [] })).unwrap(),
+            "user/limited"
+        );
+        assert_eq!(render(source, json!({})).unwrap(), "user/limited");
+    }
+
+    #[test]
+    fn renders_nested_each_with_loop_variables() {
+        let source = "{{#each groups}}{{name}}:{{#each items}}{{@index}}={{this}}{{#unless @last}},{{/unless}}{{/each}};{{/each}}";
+        let output = render(
+            source,
+            json!({ "groups": [
+                { "name": "a", "items": ["x", "y"] },
+                { "name": "b", "items": ["z"] },
+            ] }),
+        )
+        .unwrap();
+        assert_eq!(output, "a:0=x,1=y;b:0=z;");
+    }
+
+    #[test]
+    fn each_renders_else_for_empty_lists() {
+        let source = "{{#each items}}{{this}}{{else}}none{{/each}}";
+        assert_eq!(render(source, json!({ "items": [] })).unwrap(), "none");
+        assert!(matches!(
+            render(source, json!({ "items": 3 })),
+            Err(PromptTemplateError::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn looks_up_parent_scopes() {
+        let source = "{{#each users}}{{name}}@{{company}} {{/each}}";
+        let output = render(
+            source,
+            json!({ "company": "acme", "users": [{ "name": "a" }, { "name": "b", "company": "own" }] }),
+        )
+        .unwrap();
+        assert_eq!(output, "a@acme b@own ");
+    }
+
+    #[test]
+    fn renders_partials() {
+        let mut renderer = PromptRenderer::new();
+        renderer.add_partial("greeting", "Hello {{name}}").unwrap();
+
+        let output = renderer
+            .render(
+                "{{> greeting}}!",
+                &variables(json!({ "name": "Ada" })),
+                TemplateMode::Strict,
+            )
+            .unwrap();
+        assert_eq!(output, "Hello Ada!");
+
+        let missing = renderer.render("{{> other}}", &HashMap::new(), TemplateMode::Strict);
+        assert!(
+            matches!(missing, Err(PromptTemplateError::MissingPartial(name)) if name == "other")
+        );
+    }
+
+    #[test]
+    fn limits_partial_depth() {
+        let mut renderer = PromptRenderer::new();
+        renderer.add_partial("loop", "x{{> loop}}").unwrap();
+
+        let result = renderer.render("{{> loop}}", &HashMap::new(), TemplateMode::Strict);
+        assert!(matches!(result, Err(PromptTemplateError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn strips_standalone_block_lines() {
+        let source = "Items:\n{{#each items}}\n- {{this}}\n{{/each}}\nDone";
+        let output = render(source, json!({ "items": ["a", "b"] })).unwrap();
+        assert_eq!(output, "Items:\n- a\n- b\nDone");
+
+        let source = "  {{#if show}}  \nshown\n{{else}}\nhidden\n  {{/if}}";
+        assert_eq!(render(source, json!({ "show": true })).unwrap(), "shown\n");
+        assert_eq!(
+            render(source, json!({ "show": false })).unwrap(),
+            "hidden\n"
+        );
+
+        let source = "{{#if show}}\nx\n{{/if}}  ";
+        assert_eq!(render(source, json!({ "show": true })).unwrap(), "x\n");
+    }
+
+    #[test]
+    fn keeps_inline_block_tags() {
+        let source = "a {{#if show}}b{{/if}} c\n{{name}}\n";
+        let output = render(source, json!({ "show": true, "name": "n" })).unwrap();
+        assert_eq!(output, "a b c\nn\n");
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert!(matches!(
+            render("{{name", json!({})),
+            Err(PromptTemplateError::SyntaxError(_))
+        ));
+        assert!(matches!(
+            render("{{#if a}}x{{/each}}", json!({ "a": true })),
+            Err(PromptTemplateError::SyntaxError(_))
+        ));
+        assert!(matches!(
+            render("{{/if}}", json!({})),
+            Err(PromptTemplateError::SyntaxError(_))
+        ));
+        assert!(matches!(
+            render("{{#if}}x{{/if}}", json!({})),
+            Err(PromptTemplateError::SyntaxError(_))
+        ));
+    }
+}
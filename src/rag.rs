@@ -0,0 +1,291 @@
+use crate::endpoint_runner::EndpointResponse;
+use crate::llm_client::{EmbedOptions, LlmClient, LlmClientError};
+use crate::vector_store::{MetadataFilter, SearchResult, Similarity, VectorStore};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct RagResponse {
+    pub answer: EndpointResponse,
+    /// The retrieved chunks the answer was based on, best match first.
+    pub sources: Vec<SearchResult>,
+}
+
+impl RagResponse {
+    pub fn source_ids(&self) -> Vec<&str> {
+        self.sources
+            .iter()
+            .map(|source| source.id.as_str())
+            .collect()
+    }
+}
+
+/// Embeds `query` and returns the `top_k` closest chunks in `store`.
+pub async fn retrieve(
+    embedder: &dyn LlmClient,
+    model: &str,
+    store: &VectorStore,
+    query: &str,
+    top_k: usize,
+    similarity: Similarity,
+    filter: Option<&MetadataFilter>,
+) -> Result<Vec<SearchResult>, LlmClientError> {
+    let response = embedder
+        .embed(model, &[query.to_string()], &EmbedOptions::default())
+        .await?;
+
+    let vector = match response.embeddings.into_iter().next() {
+        Some(vector) => vector,
+        None => {
+            return Err(LlmClientError::ParseError(
+                "Embedding response contained no vectors".to_string(),
+            ));
+        }
+    };
+
+    Ok(store.search(&vector, top_k, similarity, filter)?)
+}
+
+/// Template variables for the retrieved chunks: `query`, `context` with the
+/// chunk texts separated by blank lines, and `chunks` for custom layouts.
+pub fn context_variables(
+    query: &str,
+    sources: &[SearchResult],
+    text_key: &str,
+) -> HashMap<String, Value> {
+    let texts: Vec<&str> = sources
+        .iter()
+        .map(|source| {
+            source
+                .metadata
+                .get(text_key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let chunks: Vec<Value> = sources
+        .iter()
+        .zip(&texts)
+        .map(|(source, text)| {
+            json!({
+                "id": source.id,
+                "text": text,
+                "score": source.score,
+                "metadata": source.metadata,
+            })
+        })
+        .collect();
+
+    HashMap::from([
+        ("query".to_string(), json!(query)),
+        ("context".to_string(), json!(texts.join("\n\n"))),
+        ("chunks".to_string(), Value::Array(chunks)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_message::ChatMessage;
+    use crate::llm_client::{EmbedResponse, LlmResponse, ModelInfo};
+    use crate::tool::Tool;
+    use crate::vector_store::VectorStoreError;
+    use async_trait::async_trait;
+    use serde_json::Map;
+    use std::sync::Mutex;
+
+    /// Embeds every input as the same fixed vector and records the model asked for.
+    struct StubEmbedder {
+        vector: Vec<f32>,
+        models: Mutex<Vec<String>>,
+    }
+
+    impl StubEmbedder {
+        fn new(vector: Vec<f32>) -> Self {
+            Self {
+                vector,
+                models: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LlmClient for StubEmbedder {
+        async fn generate(
+            &self,
+            _model: &str,
+            _system_prompt: &str,
+            _prompt: &str,
+            _json: bool,
+        ) -> Result<LlmResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("generate".to_string()))
+        }
+
+        async fn chat(
+            &self,
+            _model: &str,
+            _messages: &[ChatMessage],
+            _json: bool,
+        ) -> Result<LlmResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("chat".to_string()))
+        }
+
+        async fn chat_with_tools(
+            &self,
+            _model: &str,
+            _messages: &[ChatMessage],
+            _tools: &[Tool],
+        ) -> Result<LlmResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("chat_with_tools".to_string()))
+        }
+
+        async fn chat_structured(
+            &self,
+            _model: &str,
+            _messages: &[ChatMessage],
+            _schema: &Value,
+        ) -> Result<LlmResponse, LlmClientError> {
+            Err(LlmClientError::Unsupported("chat_structured".to_string()))
+        }
+
+        async fn embed(
+            &self,
+            model: &str,
+            input: &[String],
+            _options: &EmbedOptions,
+        ) -> Result<EmbedResponse, LlmClientError> {
+            self.models.lock().unwrap().push(model.to_string());
+            Ok(EmbedResponse {
+                model: model.to_string(),
+                embeddings: input.iter().map(|_| self.vector.clone()).collect(),
+                usage: None,
+            })
+        }
+
+        async fn list_models(&self) -> Result<Vec<ModelInfo>, LlmClientError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn metadata(text: &str, lang: &str) -> Map<String, Value> {
+        let mut metadata = Map::new();
+        metadata.insert("text".to_string(), json!(text));
+        metadata.insert("lang".to_string(), json!(lang));
+        metadata
+    }
+
+    fn store() -> VectorStore {
+        let mut store = VectorStore::new();
+        store
+            .add("north", vec![0.0, 1.0], metadata("Oslo is cold.", "en"))
+            .unwrap();
+        store
+            .add("east", vec![1.0, 0.0], metadata("Tokyo is busy.", "en"))
+            .unwrap();
+        store
+            .add(
+                "north-east",
+                vec![0.8, 0.6],
+                metadata("Helsinki is calm.", "fi"),
+            )
+            .unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn retrieves_top_k_best_first() {
+        let embedder = StubEmbedder::new(vec![1.0, 0.1]);
+
+        let sources = retrieve(
+            &embedder,
+            "embedder",
+            &store(),
+            "busy cities",
+            2,
+            Similarity::Cosine,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let ids: Vec<_> = sources.iter().map(|source| source.id.as_str()).collect();
+        assert_eq!(ids, ["east", "north-east"]);
+        assert!(sources[0].score > sources[1].score);
+        assert_eq!(*embedder.models.lock().unwrap(), ["embedder"]);
+    }
+
+    #[tokio::test]
+    async fn retrieves_only_matching_metadata() {
+        let embedder = StubEmbedder::new(vec![1.0, 0.1]);
+        let filter = MetadataFilter::new().eq("lang", json!("fi"));
+
+        let sources = retrieve(
+            &embedder,
+            "embedder",
+            &store(),
+            "busy cities",
+            2,
+            Similarity::Cosine,
+            Some(&filter),
+        )
+        .await
+        .unwrap();
+
+        let ids: Vec<_> = sources.iter().map(|source| source.id.as_str()).collect();
+        assert_eq!(ids, ["north-east"]);
+    }
+
+    #[tokio::test]
+    async fn wrong_embedding_size_keeps_store_error_as_source() {
+        use std::error::Error;
+
+        let embedder = StubEmbedder::new(vec![1.0, 0.0, 0.0]);
+
+        let error = retrieve(
+            &embedder,
+            "embedder",
+            &store(),
+            "busy cities",
+            2,
+            Similarity::Cosine,
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            LlmClientError::VectorStore(VectorStoreError::DimensionMismatch(_))
+        ));
+        assert!(error.source().unwrap().to_string().contains("3"));
+    }
+
+    #[test]
+    fn builds_template_variables() {
+        let sources = vec![
+            SearchResult {
+                id: "east".to_string(),
+                score: 0.9,
+                metadata: metadata("Tokyo is busy.", "en"),
+            },
+            SearchResult {
+                id: "north-east".to_string(),
+                score: 0.25,
+                metadata: metadata("Helsinki is calm.", "fi"),
+            },
+        ];
+
+        let variables = context_variables("Which city is busy?", &sources, "text");
+
+        assert_eq!(variables["query"], "Which city is busy?");
+        assert_eq!(variables["context"], "Tokyo is busy.\n\nHelsinki is calm.");
+        assert_eq!(
+            variables["chunks"],
+            json!([
+                { "id": "east", "text": "Tokyo is busy.", "score": 0.9f32, "metadata": { "text": "Tokyo is busy.", "lang": "en" } },
+                { "id": "north-east", "text": "Helsinki is calm.", "score": 0.25f32, "metadata": { "text": "Helsinki is calm.", "lang": "fi" } },
+            ])
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use log::debug;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Client-side request and token budgets for one server.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl RateLimitConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.requests_per_minute.is_some() || self.tokens_per_minute.is_some()
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    /// The configured limit, which server headers may lower but never raise.
+    configured: f64,
+    capacity: f64,
+    available: f64,
+    refill_per_second: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = f64::from(limit.max(1));
+        Self {
+            configured: capacity,
+            capacity,
+            available: capacity,
+            refill_per_second: capacity / 60.0,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    // more than a full bucket could never be granted, so ask for a full one
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::try_from_secs_f64(missing / self.refill_per_second)
+                .unwrap_or(Duration::from_secs(60))
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available -= amount.min(self.capacity);
+    }
+
+    fn set_limit(&mut self, limit: f64) {
+        self.capacity = limit.clamp(1.0, self.configured);
+        self.refill_per_second = self.capacity / 60.0;
+        self.available = self.available.min(self.capacity);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+/// A token-bucket limiter for requests and tokens per minute. Callers over
+/// budget wait until enough of the budget has refilled.
+#[derive(Debug)]
+pub struct RateLimiter {
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            buckets: Mutex::new(Buckets {
+                requests: config.requests_per_minute.map(TokenBucket::per_minute),
+                tokens: config.tokens_per_minute.map(TokenBucket::per_minute),
+            }),
+        }
+    }
+
+    /// Waits until one request using about `tokens` tokens fits the budget,
+    /// then takes it from the budget.
+    pub async fn acquire(&self, tokens: usize) {
+        let tokens = tokens as f64;
+
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+
+                let mut wait = Duration::ZERO;
+                if let Some(bucket) = &mut buckets.requests {
+                    bucket.refill(now);
+                    wait = wait.max(bucket.wait_for(1.0));
+                }
+                if let Some(bucket) = &mut buckets.tokens {
+                    bucket.refill(now);
+                    wait = wait.max(bucket.wait_for(tokens));
+                }
+
+                if wait.is_zero() {
+                    if let Some(bucket) = &mut buckets.requests {
+                        bucket.take(1.0);
+                    }
+                    if let Some(bucket) = &mut buckets.tokens {
+                        bucket.take(tokens);
+                    }
+                    return;
+                }
+
+                wait
+            };
+
+            debug!("Rate limit reached, waiting {wait:?}");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Follows the server's view of the budget from OpenAI's
+    /// `x-ratelimit-limit-*` and `x-ratelimit-remaining-*` headers, which also
+    /// counts requests made by other users of the same key. The headers can
+    /// only tighten the configured limits, since on a shared key they report
+    /// the limit of the whole organization.
+    pub fn update_from_headers(&self, headers: &HeaderMap) {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|value| value.is_finite())
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let Buckets { requests, tokens } = &mut *buckets;
+        let now = Instant::now();
+
+        for (bucket, kind) in [(requests, "requests"), (tokens, "tokens")] {
+            let Some(bucket) = bucket else {
+                continue;
+            };
+
+            bucket.refill(now);
+            if let Some(limit) = header(&format!("x-ratelimit-limit-{kind}")) {
+                bucket.set_limit(limit);
+            }
+            if let Some(remaining) = header(&format!("x-ratelimit-remaining-{kind}")) {
+                bucket.available = bucket.available.min(remaining.max(0.0));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn tokens(limiter: &RateLimiter) -> (f64, f64) {
+        let buckets = limiter.buckets.lock().unwrap();
+        let bucket = buckets.tokens.as_ref().unwrap();
+        (bucket.capacity, bucket.available)
+    }
+
+    #[test]
+    fn refills_over_time_up_to_capacity() {
+        let mut bucket = TokenBucket::per_minute(60);
+        let start = bucket.updated;
+        bucket.take(60.0);
+
+        bucket.refill(start + Duration::from_secs(10));
+        assert!((bucket.available - 10.0).abs() < 1e-9);
+
+        bucket.refill(start + Duration::from_secs(600));
+        assert_eq!(bucket.available, 60.0);
+    }
+
+    #[test]
+    fn waits_only_when_short() {
+        let mut bucket = TokenBucket::per_minute(60);
+        assert_eq!(bucket.wait_for(60.0), Duration::ZERO);
+
+        bucket.take(50.0);
+        assert_eq!(bucket.wait_for(10.0), Duration::ZERO);
+        assert_eq!(bucket.wait_for(15.0), Duration::from_secs(5));
+
+        // a request larger than the bucket waits for a full bucket only
+        bucket.take(10.0);
+        assert_eq!(bucket.wait_for(1000.0), Duration::from_secs(60));
+        bucket.take(1000.0);
+        assert_eq!(bucket.available, -60.0);
+    }
+
+    #[test]
+    fn headers_lower_but_never_raise_the_limit() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            requests_per_minute: None,
+            tokens_per_minute: Some(1000),
+        });
+
+        limiter.update_from_headers(&headers(&[("x-ratelimit-limit-tokens", "100000")]));
+        assert_eq!(tokens(&limiter), (1000.0, 1000.0));
+
+        limiter.update_from_headers(&headers(&[("x-ratelimit-limit-tokens", "600")]));
+        assert_eq!(tokens(&limiter), (600.0, 600.0));
+
+        limiter.update_from_headers(&headers(&[("x-ratelimit-limit-tokens", "100000")]));
+        assert_eq!(tokens(&limiter).0, 1000.0);
+    }
+
+    #[test]
+    fn remaining_header_is_clamped() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            requests_per_minute: None,
+            tokens_per_minute: Some(1000),
+        });
+
+        limiter.update_from_headers(&headers(&[("x-ratelimit-remaining-tokens", "5000")]));
+        assert!(tokens(&limiter).1 <= 1000.0);
+
+        limiter.update_from_headers(&headers(&[("x-ratelimit-remaining-tokens", "400")]));
+        assert!(tokens(&limiter).1 < 401.0);
+
+        for value in ["-1e300", "inf", "NaN", "lots"] {
+            let limiter = RateLimiter::new(&RateLimitConfig {
+                requests_per_minute: None,
+                tokens_per_minute: Some(1000),
+            });
+            let mut map = HeaderMap::new();
+            map.insert(
+                "x-ratelimit-remaining-tokens",
+                HeaderValue::from_static(value),
+            );
+            limiter.update_from_headers(&map);
+
+            let (_, available) = tokens(&limiter);
+            assert!((0.0..=1000.0).contains(&available), "{value}: {available}");
+        }
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_refill() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            requests_per_minute: Some(600),
+            tokens_per_minute: None,
+        });
+        limiter.update_from_headers(&headers(&[("x-ratelimit-remaining-requests", "0")]));
+
+        // 600 per minute refills one request every 100ms
+        let start = Instant::now();
+        limiter.acquire(0).await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -73,7 +73,7 @@ fn read_config(path: &PathBuf) -> Result<SecretsConfig, Error> {
         Ok(contents) => contents,
         Err(e) => {
             return Err(Error::new(
-                ErrorKind::NotFound,
+                e.kind(),
                 format!("Unable to read configuration. {}", e),
             ))
         }
@@ -83,7 +83,7 @@ fn read_config(path: &PathBuf) -> Result<SecretsConfig, Error> {
         Ok(token) => token,
         Err(e) => {
             return Err(Error::new(
-                ErrorKind::NotFound,
+                ErrorKind::InvalidData,
                 format!("Unable to parse configuration. {}", e),
             ))
         }
@@ -1,4 +1,7 @@
-use serde::Deserialize;
+use crate::rate_limiter::RateLimitConfig;
+use crate::vector_store::Similarity;
+use crate::web_api_client::RetryPolicy;
+use serde::{Deserialize, Serialize};
 use std::fs::read_to_string;
 use std::io::{Error, ErrorKind};
 use std::path::PathBuf;
@@ -29,15 +32,19 @@ pub struct Endpoint {
     pub template: String,
     pub system_prompt: String,
     pub user_prompt: String,
+    pub json: bool,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct EndpointConfig {
     pub path: String,
+    /// Prompt rendering mode: "strict" (default when empty), "lenient" or "raw".
     pub template: String,
     pub server: String,
     pub system_prompt: String,
     pub user_prompt: String,
+    #[serde(default)]
+    pub json: bool,
 }
 
 impl EndpointConfig {
@@ -47,25 +54,115 @@ impl EndpointConfig {
             template: self.template.clone(),
             system_prompt: self.system_prompt.clone(),
             user_prompt: self.user_prompt.clone(),
+            json: self.json,
         }
     }
 }
 
+fn default_top_k() -> usize {
+    4
+}
+
+fn default_text_key() -> String {
+    "text".to_string()
+}
+
+/// An endpoint that answers a query from the chunks of a vector store. The
+/// prompts can use `{{query}}`, `{{context}}` (the retrieved chunk texts) and
+/// `{{#each chunks}}` with `id`, `text`, `score` and `metadata` per chunk.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RagEndpointConfig {
+    pub path: String,
+    /// Prompt rendering mode: "strict" (default when empty), "lenient" or "raw".
+    pub template: String,
+    pub server: String,
+    /// Server used to embed the query, defaults to `server`.
+    pub embedding_server: Option<String>,
+    /// The model that embedded the store. Queries must use the same one.
+    pub embedding_model: String,
+    /// JSONL file written by `VectorStore::save_jsonl`.
+    pub store: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    #[serde(default)]
+    pub similarity: Similarity,
+    /// Metadata key holding the text of each chunk.
+    #[serde(default = "default_text_key")]
+    pub text_key: String,
+    pub system_prompt: String,
+    pub user_prompt: String,
+    #[serde(default)]
+    pub json: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SafetySetting {
+    pub category: String,
+    pub threshold: String,
+}
+
+/// Azure OpenAI routes requests by deployment and versions its API by query parameter.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AzureConfig {
+    pub deployment: String,
+    pub api_version: String,
+}
+
+/// URL paths of the API operations, relative to `base_api_url`. Unset paths
+/// use the provider's standard ones, e.g. `v1/chat/completions` for OpenAI.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EndpointPaths {
+    pub chat: Option<String>,
+    /// Ollama only.
+    pub generate: Option<String>,
+    pub embeddings: Option<String>,
+    pub models: Option<String>,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct ServerConfig {
     pub name: String,
     pub model: String,
     pub api_type: String,
+    /// The server root, without the API version: the standard paths already
+    /// start with it, so `https://api.openai.com/v1` would request
+    /// `/v1/v1/chat/completions`. Any other path, such as a gateway prefix,
+    /// is kept in front of the operation path.
     pub base_api_url: String,
     pub secret: Option<String>,
     pub connection_timeout: Option<u64>,
     pub deadline_timeout: Option<u64>,
+    /// Requests allowed in flight at once, shared by the clients of one `ClientFactory`.
+    pub max_concurrent_requests: Option<usize>,
+    /// Requests allowed to wait for a slot; more fail at once. Unbounded if unset.
+    pub max_queued_requests: Option<usize>,
+    /// Seconds a request may wait for a slot. Unbounded if unset.
+    pub queue_timeout: Option<u64>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// e.g. `{ requests_per_minute = 500, tokens_per_minute = 30000 }`.
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// Output token limit. Anthropic requires one, so a default is used there.
+    pub max_tokens: Option<usize>,
+    /// Gemini content filters, e.g. `{ category = "HARM_CATEGORY_HARASSMENT", threshold = "BLOCK_NONE" }`.
+    #[serde(default)]
+    pub safety_settings: Vec<SafetySetting>,
+    /// Required when `api_type = "azure"`.
+    pub azure: Option<AzureConfig>,
+    /// Overrides for servers that mount the API elsewhere. Not used for Azure or Gemini.
+    #[serde(default)]
+    pub paths: EndpointPaths,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Settings {
     pub servers: Vec<ServerConfig>,
     pub endpoints: Vec<EndpointConfig>,
+    #[serde(default)]
+    pub rag_endpoints: Vec<RagEndpointConfig>,
+    /// Directory of prompt files usable as `{{> name}}` partials, keyed by file stem.
+    pub prompts_dir: Option<String>,
 }
 
 impl Settings {
@@ -81,6 +178,18 @@ impl Settings {
         ))
     }
 
+    pub fn get_rag_endpoint_by_path(&self, path: &str) -> Result<RagEndpointConfig, Error> {
+        for endpoint in &self.rag_endpoints {
+            if endpoint.path == path {
+                return Ok(endpoint.clone());
+            }
+        }
+        Err(Error::new(
+            ErrorKind::NotFound,
+            format!("RAG endpoint {path} not found"),
+        ))
+    }
+
     pub fn get_server_config_by_name(&self, name: &str) -> Result<ServerConfig, Error> {
         for server in &self.servers {
             if server.name == name {
@@ -105,7 +214,7 @@ impl Settings {
             Ok(contents) => contents,
             Err(e) => {
                 return Err(Error::new(
-                    ErrorKind::NotFound,
+                    e.kind(),
                     format!("Unable to read configuration. {e}"),
                 ));
             }
@@ -115,7 +224,7 @@ impl Settings {
             Ok(token) => token,
             Err(e) => {
                 return Err(Error::new(
-                    ErrorKind::NotFound,
+                    ErrorKind::InvalidData,
                     format!("Unable to parse configuration. {e}"),
                 ));
             }
@@ -0,0 +1,178 @@
+use crate::streaming::{ByteStream, lines};
+use crate::web_api_client::WebApiClientError;
+use futures::stream::{self, BoxStream, StreamExt};
+
+/// One dispatched `text/event-stream` event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+struct EventState {
+    lines: BoxStream<'static, Result<String, WebApiClientError>>,
+    pending: SseEvent,
+    has_data: bool,
+    finished: bool,
+}
+
+/// Groups a server-sent events body into events. Multi-line `data:` fields
+/// are joined with `\n` and comment lines are ignored.
+pub fn events(bytes: ByteStream) -> BoxStream<'static, Result<SseEvent, WebApiClientError>> {
+    let state = EventState {
+        lines: lines(bytes),
+        pending: SseEvent::default(),
+        has_data: false,
+        finished: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        if state.finished {
+            return None;
+        }
+
+        loop {
+            let line = match state.lines.next().await {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    state.finished = true;
+                    return Some((Err(e), state));
+                }
+                None => {
+                    state.finished = true;
+                    return state.take_event().map(|event| (Ok(event), state));
+                }
+            };
+
+            if line.is_empty() {
+                match state.take_event() {
+                    Some(event) => return Some((Ok(event), state)),
+                    None => continue,
+                }
+            }
+
+            state.apply_line(&line);
+        }
+    })
+    .boxed()
+}
+
+impl EventState {
+    fn apply_line(&mut self, line: &str) {
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "data" => {
+                if self.has_data {
+                    self.pending.data.push('\n');
+                }
+                self.pending.data.push_str(value);
+                self.has_data = true;
+            }
+            "event" => self.pending.event = Some(value.to_string()),
+            "id" => self.pending.id = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    // events without any data lines are not dispatched
+    fn take_event(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.pending);
+        let has_data = std::mem::replace(&mut self.has_data, false);
+
+        if has_data { Some(event) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    // feeds the body in small chunks so events and lines span chunk boundaries
+    async fn parse(body: &str) -> Vec<SseEvent> {
+        let chunks: Vec<Result<Bytes, WebApiClientError>> = body
+            .as_bytes()
+            .chunks(3)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+
+        events(stream::iter(chunks).boxed())
+            .map(|event| event.unwrap())
+            .collect()
+            .await
+    }
+
+    fn data(data: &str) -> SseEvent {
+        SseEvent {
+            data: data.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn parses_events() {
+        let events = parse("data: one\n\nevent: delta\nid: 7\ndata:two\n\n").await;
+
+        assert_eq!(
+            events,
+            [
+                data("one"),
+                SseEvent {
+                    event: Some("delta".to_string()),
+                    data: "two".to_string(),
+                    id: Some("7".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn joins_multi_line_data() {
+        let events = parse("data: {\"a\":\ndata:  1}\ndata\n\n").await;
+        assert_eq!(events, [data("{\"a\":\n 1}\n")]);
+    }
+
+    #[tokio::test]
+    async fn ignores_comments_and_events_without_data() {
+        let events = parse(": keep-alive\n\nevent: ping\n\ndata: x\n: note\n\n\n\n").await;
+        assert_eq!(events, [data("x")]);
+    }
+
+    #[tokio::test]
+    async fn dispatches_last_event_without_trailing_blank_line() {
+        let events = parse("data: one\n\ndata: [DONE]").await;
+        assert_eq!(events, [data("one"), data("[DONE]")]);
+    }
+
+    #[tokio::test]
+    async fn handles_crlf_line_endings() {
+        let events = parse("event: a\r\ndata: one\r\n\r\ndata: two\r\n\r\n").await;
+
+        assert_eq!(events[0].event.as_deref(), Some("a"));
+        assert_eq!(events[0].data, "one");
+        assert_eq!(events[1], data("two"));
+    }
+
+    #[tokio::test]
+    async fn stops_after_stream_error() {
+        let chunks: Vec<Result<Bytes, WebApiClientError>> = vec![
+            Ok(Bytes::from_static(b"data: one\n\n")),
+            Err(WebApiClientError::PostFailed("reset".to_string())),
+            Ok(Bytes::from_static(b"data: two\n\n")),
+        ];
+        let results: Vec<_> = events(stream::iter(chunks).boxed()).collect().await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), &data("one"));
+        assert!(results[1].is_err());
+    }
+}
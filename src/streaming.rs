@@ -0,0 +1,174 @@
+use crate::web_api_client::WebApiClientError;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::de::DeserializeOwned;
+
+pub type ByteStream = BoxStream<'static, Result<Bytes, WebApiClientError>>;
+
+struct LineState {
+    bytes: ByteStream,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+/// Splits a response body into lines as they arrive, without their line endings.
+pub fn lines(bytes: ByteStream) -> BoxStream<'static, Result<String, WebApiClientError>> {
+    let state = LineState {
+        bytes,
+        buffer: Vec::new(),
+        finished: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(newline) = state.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=newline).collect();
+                return Some((decode_line(&line), state));
+            }
+
+            if state.finished {
+                if state.buffer.is_empty() {
+                    return None;
+                }
+                let rest = std::mem::take(&mut state.buffer);
+                return Some((decode_line(&rest), state));
+            }
+
+            match state.bytes.next().await {
+                Some(Ok(chunk)) => state.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    state.finished = true;
+                    state.buffer.clear();
+                    return Some((Err(e), state));
+                }
+                None => state.finished = true,
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Parses a newline-delimited JSON body into one item per line.
+pub fn json_lines<T: DeserializeOwned + Send + 'static>(
+    bytes: ByteStream,
+) -> BoxStream<'static, Result<T, WebApiClientError>> {
+    lines(bytes)
+        .filter_map(|line| async move {
+            match line {
+                Ok(line) if line.trim().is_empty() => None,
+                Ok(line) => Some(serde_json::from_str(&line).map_err(|e| {
+                    WebApiClientError::ParseError(format!("Failed to parse streamed line: {e}"))
+                })),
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .boxed()
+}
+
+fn decode_line(line: &[u8]) -> Result<String, WebApiClientError> {
+    match std::str::from_utf8(line) {
+        Ok(line) => Ok(line.trim_end_matches(['\n', '\r']).to_string()),
+        Err(e) => Err(WebApiClientError::ParseError(format!(
+            "Streamed line is not valid UTF-8: {e}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn body(chunks: &[&[u8]]) -> ByteStream {
+        let chunks: Vec<Result<Bytes, WebApiClientError>> = chunks
+            .iter()
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        stream::iter(chunks).boxed()
+    }
+
+    async fn collect_lines(chunks: &[&[u8]]) -> Vec<String> {
+        lines(body(chunks))
+            .map(|line| line.unwrap())
+            .collect()
+            .await
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Chunk {
+        n: u32,
+    }
+
+    #[tokio::test]
+    async fn joins_lines_split_across_chunks() {
+        let lines = collect_lines(&[b"{\"n\":", b"1}\n{\"n\"", b":2}\n"]).await;
+        assert_eq!(lines, [r#"{"n":1}"#, r#"{"n":2}"#]);
+    }
+
+    #[tokio::test]
+    async fn joins_multibyte_characters_split_across_chunks() {
+        let text = "grüße 🦀\n".as_bytes();
+        // split inside both the ü and the crab
+        let lines = collect_lines(&[&text[..3], &text[3..8], &text[8..]]).await;
+        assert_eq!(lines, ["grüße 🦀"]);
+    }
+
+    #[tokio::test]
+    async fn strips_crlf_endings() {
+        let lines = collect_lines(&[b"one\r\ntwo\r", b"\nthree\n"]).await;
+        assert_eq!(lines, ["one", "two", "three"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_final_line_without_newline() {
+        let lines = collect_lines(&[b"one\ntw", b"o"]).await;
+        assert_eq!(lines, ["one", "two"]);
+
+        assert!(collect_lines(&[]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_invalid_utf8() {
+        let lines: Vec<_> = lines(body(&[b"ok\n\xff\xfe\n"])).collect().await;
+        assert_eq!(lines.len(), 2);
+        assert!(matches!(lines[1], Err(WebApiClientError::ParseError(_))));
+    }
+
+    #[tokio::test]
+    async fn ends_after_a_read_error() {
+        let chunks: Vec<Result<Bytes, WebApiClientError>> = vec![
+            Ok(Bytes::from_static(b"one\ntw")),
+            Err(WebApiClientError::ConnectionFailed("reset".to_string())),
+            Ok(Bytes::from_static(b"o\n")),
+        ];
+
+        let lines: Vec<_> = lines(stream::iter(chunks).boxed()).collect().await;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].as_ref().unwrap(), "one");
+        assert!(matches!(
+            lines[1],
+            Err(WebApiClientError::ConnectionFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn parses_json_lines_skipping_blank_ones() {
+        let items: Vec<Chunk> = json_lines(body(&[b"{\"n\":1}\n\n  \r\n{\"n\":", b"2}"]))
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(items, [Chunk { n: 1 }, Chunk { n: 2 }]);
+    }
+
+    #[tokio::test]
+    async fn bad_json_line_does_not_end_the_stream() {
+        let items: Vec<Result<Chunk, _>> = json_lines(body(&[b"{\"n\":1}\nnot json\n{\"n\":3}\n"]))
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_ref().unwrap(), &Chunk { n: 1 });
+        assert!(matches!(items[1], Err(WebApiClientError::ParseError(_))));
+        assert_eq!(items[2].as_ref().unwrap(), &Chunk { n: 3 });
+    }
+}
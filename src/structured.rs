@@ -0,0 +1,313 @@
+use crate::chat_message::ChatMessage;
+use crate::json_repair::repair_json;
+use crate::llm_client::{LlmClient, LlmClientError};
+use async_trait::async_trait;
+use log::debug;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub const DEFAULT_STRUCTURED_ATTEMPTS: usize = 3;
+
+/// The JSON Schema of `T`, without the `$schema` meta key some providers reject.
+pub fn schema_for<T: JsonSchema>() -> Value {
+    let mut schema = schemars::schema_for!(T).to_value();
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$schema");
+    }
+    schema
+}
+
+/// Adapts `schema` to OpenAI's strict structured outputs: every object lists
+/// all of its properties as required and allows no others, and `oneOf` becomes
+/// `anyOf`. `Option` fields stay nullable, so the model sends `null` for them.
+pub fn strict_schema(schema: &Value) -> Value {
+    let mut schema = schema.clone();
+    make_strict(&mut schema);
+    schema
+}
+
+fn make_strict(schema: &mut Value) {
+    let object = match schema {
+        Value::Object(object) => object,
+        Value::Array(items) => {
+            items.iter_mut().for_each(make_strict);
+            return;
+        }
+        _ => return,
+    };
+
+    if let Some(one_of) = object.remove("oneOf") {
+        object.insert("anyOf".to_string(), one_of);
+    }
+
+    if let Some(Value::Object(properties)) = object.get("properties") {
+        let required: Vec<Value> = properties.keys().cloned().map(Value::String).collect();
+        object.insert("required".to_string(), Value::Array(required));
+        object.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+
+    for key in ["properties", "$defs", "definitions"] {
+        if let Some(Value::Object(schemas)) = object.get_mut(key) {
+            schemas.values_mut().for_each(make_strict);
+        }
+    }
+
+    for key in ["items", "anyOf", "allOf", "prefixItems"] {
+        if let Some(value) = object.get_mut(key) {
+            make_strict(value);
+        }
+    }
+}
+
+/// A provider-safe name for a schema, taken from its `title`.
+pub fn schema_name(schema: &Value) -> String {
+    let name: String = schema
+        .get("title")
+        .and_then(Value::as_str)
+        .unwrap_or("response")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .take(64)
+        .collect();
+
+    if name.is_empty() {
+        "response".to_string()
+    } else {
+        name
+    }
+}
+
+/// Parses `content` as `T`, retrying once on a locally repaired copy.
+pub fn parse_structured<T: DeserializeOwned>(
+    content: &str,
+    schema: &Value,
+) -> Result<T, LlmClientError> {
+    let error = match serde_json::from_str(content) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+
+    let repaired = repair_json(content);
+    if repaired != content
+        && let Ok(value) = serde_json::from_str(&repaired)
+    {
+        debug!("Parsed structured output after local JSON repair");
+        return Ok(value);
+    }
+
+    Err(LlmClientError::SchemaMismatch(format!(
+        "Response does not match the {} schema: {error}. Response was: {content}",
+        schema_name(schema)
+    )))
+}
+
+/// Typed structured output for every `LlmClient`, including `dyn LlmClient`.
+///
+/// Replies that fail to parse are first repaired locally. If that does not
+/// help, the error is sent back to the model as a follow-up turn asking it to
+/// fix its output, up to `DEFAULT_STRUCTURED_ATTEMPTS` model calls in total.
+#[async_trait]
+pub trait StructuredOutput {
+    async fn generate_structured<T>(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+    ) -> Result<T, LlmClientError>
+    where
+        T: DeserializeOwned + JsonSchema + Send;
+
+    async fn chat_structured_as<T>(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+    ) -> Result<T, LlmClientError>
+    where
+        T: DeserializeOwned + JsonSchema + Send;
+
+    async fn chat_structured_with_attempts<T>(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        max_attempts: usize,
+    ) -> Result<T, LlmClientError>
+    where
+        T: DeserializeOwned + JsonSchema + Send;
+}
+
+#[async_trait]
+impl<C: LlmClient + ?Sized> StructuredOutput for C {
+    async fn generate_structured<T>(
+        &self,
+        model: &str,
+        system_prompt: &str,
+        prompt: &str,
+    ) -> Result<T, LlmClientError>
+    where
+        T: DeserializeOwned + JsonSchema + Send,
+    {
+        let messages = [
+            ChatMessage::system(system_prompt),
+            ChatMessage::user(prompt),
+        ];
+        self.chat_structured_as(model, &messages).await
+    }
+
+    async fn chat_structured_as<T>(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+    ) -> Result<T, LlmClientError>
+    where
+        T: DeserializeOwned + JsonSchema + Send,
+    {
+        self.chat_structured_with_attempts(model, messages, DEFAULT_STRUCTURED_ATTEMPTS)
+            .await
+    }
+
+    async fn chat_structured_with_attempts<T>(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        max_attempts: usize,
+    ) -> Result<T, LlmClientError>
+    where
+        T: DeserializeOwned + JsonSchema + Send,
+    {
+        let schema = schema_for::<T>();
+        let mut messages = messages.to_vec();
+        let mut attempt = 1;
+
+        loop {
+            let response = self.chat_structured(model, &messages, &schema).await?;
+
+            let error = match parse_structured(&response.content, &schema) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+
+            if attempt >= max_attempts {
+                return Err(error);
+            }
+
+            debug!("Structured output attempt {attempt} failed: {error}");
+            messages.push(ChatMessage::assistant(&response.content));
+            messages.push(ChatMessage::user(&format!(
+                "Your response could not be used: {error}\n\
+                 Reply again with only JSON that matches the required schema."
+            )));
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema, Debug, PartialEq)]
+    struct Answer {
+        name: String,
+        score: u32,
+    }
+
+    #[test]
+    fn parses_valid_and_repaired_output() {
+        let schema = schema_for::<Answer>();
+        let expected = Answer {
+            name: "a".to_string(),
+            score: 3,
+        };
+
+        let valid: Answer = parse_structured(r#"{"name": "a", "score": 3}"#, &schema).unwrap();
+        assert_eq!(valid, expected);
+
+        let fenced = "```json\n{\"name\": \"a\", \"score\": 3,}\n```";
+        let repaired: Answer = parse_structured(fenced, &schema).unwrap();
+        assert_eq!(repaired, expected);
+    }
+
+    #[test]
+    fn reports_schema_mismatch() {
+        let schema = schema_for::<Answer>();
+
+        let result = parse_structured::<Answer>(r#"{"name": "a"}"#, &schema);
+        assert!(
+            matches!(result, Err(LlmClientError::SchemaMismatch(msg)) if msg.contains("Answer"))
+        );
+
+        let result = parse_structured::<Answer>("I cannot answer that", &schema);
+        assert!(matches!(result, Err(LlmClientError::SchemaMismatch(_))));
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Report {
+        title: String,
+        note: Option<String>,
+        #[serde(default)]
+        tags: Vec<String>,
+        answers: Vec<Answer>,
+        verdict: Verdict,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    enum Verdict {
+        Pass,
+        Fail { reason: String },
+    }
+
+    fn assert_strict_objects(schema: &Value) {
+        match schema {
+            Value::Object(object) => {
+                assert!(!object.contains_key("oneOf"), "oneOf left in {schema}");
+                if let Some(Value::Object(properties)) = object.get("properties") {
+                    assert_eq!(object["additionalProperties"], false);
+                    let required: Vec<&str> = object["required"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect();
+                    assert!(
+                        properties
+                            .keys()
+                            .all(|key| required.contains(&key.as_str()))
+                    );
+                }
+                object.values().for_each(assert_strict_objects);
+            }
+            Value::Array(items) => items.iter().for_each(assert_strict_objects),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn strict_schema_requires_all_properties() {
+        let schema = strict_schema(&schema_for::<Report>());
+
+        assert_strict_objects(&schema);
+        assert_eq!(
+            schema["required"],
+            serde_json::json!(["answers", "note", "tags", "title", "verdict"])
+        );
+        assert_eq!(
+            schema["properties"]["note"]["type"],
+            serde_json::json!(["string", "null"])
+        );
+        assert_eq!(schema["$defs"]["Answer"]["additionalProperties"], false);
+    }
+
+    #[test]
+    fn schema_name_is_provider_safe() {
+        assert_eq!(schema_name(&schema_for::<Answer>()), "Answer");
+        assert_eq!(
+            schema_name(&serde_json::json!({ "title": "My Answer!" })),
+            "MyAnswer"
+        );
+        assert_eq!(schema_name(&serde_json::json!({})), "response");
+    }
+}
@@ -0,0 +1,485 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Rough token count for budgeting chunks, about four characters per token.
+pub fn approximate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitBy {
+    /// `chunk_size` characters per chunk.
+    Characters,
+    /// `chunk_size` approximate tokens per chunk, breaking between words.
+    Tokens,
+    /// Whole sentences up to `chunk_size` characters.
+    Sentences,
+    /// Whole paragraphs up to `chunk_size` characters.
+    Paragraphs,
+    /// One chunk per markdown section, split by paragraph when longer than
+    /// `chunk_size` characters.
+    MarkdownHeadings,
+}
+
+/// A piece of a source document. `start` and `end` are byte offsets of
+/// `text` in the source.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub id: String,
+    pub index: usize,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    pub metadata: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSplitter {
+    split_by: SplitBy,
+    chunk_size: usize,
+    chunk_overlap: usize,
+}
+
+impl TextSplitter {
+    pub fn new(split_by: SplitBy, chunk_size: usize) -> Self {
+        Self {
+            split_by,
+            chunk_size: chunk_size.max(1),
+            chunk_overlap: 0,
+        }
+    }
+
+    /// Repeats up to `chunk_overlap` units (characters or tokens) of the end
+    /// of each chunk at the start of the next one.
+    pub fn with_overlap(mut self, chunk_overlap: usize) -> Self {
+        self.chunk_overlap = chunk_overlap.min(self.chunk_size - 1);
+        self
+    }
+
+    /// Splits `text` into chunks with ids `{source_id}#{index}`. Every chunk
+    /// gets a copy of `metadata` plus a `source` key.
+    pub fn split(&self, source_id: &str, text: &str, metadata: &Map<String, Value>) -> Vec<Chunk> {
+        let mut metadata = metadata.clone();
+        metadata.insert("source".to_string(), json!(source_id));
+
+        let spans: Vec<(usize, usize, Map<String, Value>)> = match self.split_by {
+            SplitBy::MarkdownHeadings => self.split_markdown(text, &metadata),
+            _ => {
+                let units = match self.split_by {
+                    SplitBy::Characters => character_units(text),
+                    SplitBy::Tokens => word_units(text),
+                    SplitBy::Sentences => sentence_units(text),
+                    _ => paragraph_units(text, 0, text.len()),
+                };
+                self.merge(text, units)
+                    .into_iter()
+                    .map(|(start, end)| (start, end, metadata.clone()))
+                    .collect()
+            }
+        };
+
+        spans
+            .into_iter()
+            .filter_map(|(start, end, metadata)| trim_span(text, start, end).map(|s| (s, metadata)))
+            .enumerate()
+            .map(|(index, ((start, end), metadata))| Chunk {
+                id: format!("{source_id}#{index}"),
+                index,
+                text: text[start..end].to_string(),
+                start,
+                end,
+                metadata,
+            })
+            .collect()
+    }
+
+    fn size(&self, text: &str) -> usize {
+        match self.split_by {
+            SplitBy::Tokens => approximate_tokens(text.trim()).max(1),
+            _ => text.chars().count(),
+        }
+    }
+
+    /// Greedily joins consecutive units into spans of at most `chunk_size`,
+    /// stepping back over up to `chunk_overlap` worth of units between spans.
+    fn merge(&self, text: &str, units: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        let units = self.split_oversized(text, units);
+        let sizes: Vec<usize> = units
+            .iter()
+            .map(|(s, e)| self.size(&text[*s..*e]))
+            .collect();
+
+        let mut spans = Vec::new();
+        let mut i = 0;
+        while i < units.len() {
+            let mut size = 0;
+            let mut j = i;
+            while j < units.len() && (j == i || size + sizes[j] <= self.chunk_size) {
+                size += sizes[j];
+                j += 1;
+            }
+            spans.push((units[i].0, units[j - 1].1));
+
+            if j == units.len() {
+                break;
+            }
+
+            let mut next = j;
+            let mut overlap = 0;
+            while next > i + 1 && overlap + sizes[next - 1] <= self.chunk_overlap {
+                overlap += sizes[next - 1];
+                next -= 1;
+            }
+            i = next;
+        }
+
+        spans
+    }
+
+    // units longer than a whole chunk are cut into character windows
+    fn split_oversized(&self, text: &str, units: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        let window = match self.split_by {
+            SplitBy::Tokens => self.chunk_size * CHARS_PER_TOKEN,
+            _ => self.chunk_size,
+        };
+
+        let mut result = Vec::with_capacity(units.len());
+        for (start, end) in units {
+            if self.size(&text[start..end]) <= self.chunk_size {
+                result.push((start, end));
+                continue;
+            }
+
+            let mut boundaries: Vec<usize> = text[start..end]
+                .char_indices()
+                .map(|(i, _)| start + i)
+                .step_by(window)
+                .collect();
+            boundaries.push(end);
+            result.extend(boundaries.windows(2).map(|w| (w[0], w[1])));
+        }
+
+        result
+    }
+
+    fn split_markdown(
+        &self,
+        text: &str,
+        metadata: &Map<String, Value>,
+    ) -> Vec<(usize, usize, Map<String, Value>)> {
+        let mut spans = Vec::new();
+
+        for section in markdown_sections(text) {
+            let mut metadata = metadata.clone();
+            if let Some(heading) = section.headings.last() {
+                metadata.insert("heading".to_string(), json!(heading));
+                metadata.insert("headings".to_string(), json!(section.headings));
+            }
+
+            let section_text = &text[section.start..section.end];
+            if section_text.chars().count() <= self.chunk_size {
+                spans.push((section.start, section.end, metadata));
+                continue;
+            }
+
+            let units = paragraph_units(text, section.start, section.end);
+            for (start, end) in self.merge(text, units) {
+                spans.push((start, end, metadata.clone()));
+            }
+        }
+
+        spans
+    }
+}
+
+fn trim_span(text: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let slice = &text[start..end];
+    let trimmed = slice.trim_start();
+    let start = start + (slice.len() - trimmed.len());
+    let end = start + trimmed.trim_end().len();
+
+    if start == end {
+        None
+    } else {
+        Some((start, end))
+    }
+}
+
+fn character_units(text: &str) -> Vec<(usize, usize)> {
+    text.char_indices()
+        .map(|(i, c)| (i, i + c.len_utf8()))
+        .collect()
+}
+
+// each word keeps the whitespace that follows it, so units stay contiguous
+fn word_units(text: &str) -> Vec<(usize, usize)> {
+    let mut units = Vec::new();
+    let mut start = 0;
+    let mut in_space = false;
+
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            in_space = true;
+        } else if in_space {
+            units.push((start, i));
+            start = i;
+            in_space = false;
+        }
+    }
+
+    if start < text.len() {
+        units.push((start, text.len()));
+    }
+
+    units
+}
+
+fn sentence_units(text: &str) -> Vec<(usize, usize)> {
+    let mut units = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        let ends_sentence = matches!(c, '.' | '!' | '?')
+            || (c == '\n' && chars.peek().is_some_and(|(_, next)| *next == '\n'));
+        if !ends_sentence || !chars.peek().is_some_and(|(_, next)| next.is_whitespace()) {
+            continue;
+        }
+
+        while let Some((_, next)) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+
+        let end = chars.peek().map_or(text.len(), |(i, _)| *i);
+        units.push((start, end));
+        start = end;
+    }
+
+    if start < text.len() {
+        units.push((start, text.len()));
+    }
+
+    units
+}
+
+fn paragraph_units(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut units = Vec::new();
+    let mut unit_start = start;
+    let mut offset = start;
+
+    for line in text[start..end].split_inclusive('\n') {
+        offset += line.len();
+        if line.trim().is_empty() && offset > unit_start + line.len() {
+            units.push((unit_start, offset));
+            unit_start = offset;
+        }
+    }
+
+    if unit_start < end {
+        units.push((unit_start, end));
+    }
+
+    units
+}
+
+struct MarkdownSection {
+    start: usize,
+    end: usize,
+    headings: Vec<String>,
+}
+
+fn heading_level(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+
+    if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        Some((level, rest.trim().trim_end_matches('#').trim_end()))
+    } else {
+        None
+    }
+}
+
+// headings inside fenced code blocks are ignored
+fn markdown_sections(text: &str) -> Vec<MarkdownSection> {
+    let mut sections = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut section_start = 0;
+    let mut offset = 0;
+    let mut in_fence = false;
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_end();
+        if trimmed.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        if let Some((level, title)) = heading_level(trimmed) {
+            if line_start > section_start {
+                sections.push(MarkdownSection {
+                    start: section_start,
+                    end: line_start,
+                    headings: path.iter().map(|(_, t)| t.clone()).collect(),
+                });
+            }
+
+            path.retain(|(l, _)| *l < level);
+            path.push((level, title.to_string()));
+            section_start = line_start;
+        }
+    }
+
+    if section_start < text.len() {
+        sections.push(MarkdownSection {
+            start: section_start,
+            end: text.len(),
+            headings: path.iter().map(|(_, t)| t.clone()).collect(),
+        });
+    }
+
+    sections
+}
+
+/// The chunk texts in order, ready to pass to `LlmClient::embed`.
+pub fn chunk_texts(chunks: &[Chunk]) -> Vec<String> {
+    chunks.iter().map(|chunk| chunk.text.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(split_by: SplitBy, chunk_size: usize, overlap: usize, text: &str) -> Vec<Chunk> {
+        TextSplitter::new(split_by, chunk_size)
+            .with_overlap(overlap)
+            .split("doc", text, &Map::new())
+    }
+
+    fn texts(chunks: &[Chunk]) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+    }
+
+    fn assert_offsets(text: &str, chunks: &[Chunk]) {
+        for chunk in chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn splits_characters_with_overlap() {
+        let text = "abcdefghij";
+        let chunks = split(SplitBy::Characters, 4, 2, text);
+
+        assert_eq!(texts(&chunks), ["abcd", "cdef", "efgh", "ghij"]);
+        assert_eq!(chunks[1].id, "doc#1");
+        assert_eq!(chunks[1].metadata["source"], "doc");
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn overlap_always_makes_progress() {
+        let chunks = split(SplitBy::Characters, 3, 10, "abcdef");
+        assert_eq!(texts(&chunks), ["abc", "bcd", "cde", "def"]);
+    }
+
+    #[test]
+    fn splits_tokens_between_words() {
+        let text = "one two three four five";
+        let chunks = split(SplitBy::Tokens, 3, 1, text);
+
+        // "three" is about two tokens, more than the overlap allows
+        assert_eq!(texts(&chunks), ["one two", "two three", "four five"]);
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn splits_oversized_multibyte_units() {
+        let text = "ééééé日本語日本 ok";
+        let chunks = split(SplitBy::Sentences, 4, 0, text);
+
+        assert_eq!(texts(&chunks), ["éééé", "é日本語", "日本 o", "k"]);
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn splits_sentences() {
+        let text = "First one. Second one! Third?\n\nFourth";
+        let chunks = split(SplitBy::Sentences, 25, 0, text);
+
+        assert_eq!(
+            texts(&chunks),
+            ["First one. Second one!", "Third?\n\nFourth"]
+        );
+        assert_offsets(text, &chunks);
+
+        // a period not followed by whitespace does not end a sentence
+        let chunks = split(SplitBy::Sentences, 14, 0, "Pi is 3.14 ok. Next.");
+        assert_eq!(texts(&chunks), ["Pi is 3.14 ok.", "Next."]);
+    }
+
+    #[test]
+    fn splits_paragraphs() {
+        let text = "Para one\nline two\n\nPara two\n\n\nPara three";
+        let chunks = split(SplitBy::Paragraphs, 20, 0, text);
+
+        assert_eq!(
+            texts(&chunks),
+            ["Para one\nline two", "Para two", "Para three"]
+        );
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn splits_markdown_sections() {
+        let text = "Intro\n# Title\nText\n```\n# not a heading\n```\n## Sub\nMore\n";
+        let chunks = split(SplitBy::MarkdownHeadings, 100, 0, text);
+
+        assert_eq!(
+            texts(&chunks),
+            [
+                "Intro",
+                "# Title\nText\n```\n# not a heading\n```",
+                "## Sub\nMore"
+            ]
+        );
+        assert!(chunks[0].metadata.get("heading").is_none());
+        assert_eq!(chunks[1].metadata["heading"], "Title");
+        assert_eq!(chunks[2].metadata["headings"], json!(["Title", "Sub"]));
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn splits_long_markdown_sections_by_paragraph() {
+        let text = "# Title\n\nFirst paragraph.\n\nSecond paragraph.";
+        let chunks = split(SplitBy::MarkdownHeadings, 20, 0, text);
+
+        assert_eq!(
+            texts(&chunks),
+            ["# Title", "First paragraph.", "Second paragraph."]
+        );
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk.metadata["heading"] == "Title")
+        );
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn skips_blank_chunks() {
+        assert!(split(SplitBy::Paragraphs, 10, 0, "  \n\n  ").is_empty());
+        assert!(split(SplitBy::Characters, 10, 0, "").is_empty());
+    }
+}
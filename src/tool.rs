@@ -0,0 +1,82 @@
+use crate::llm_client::LlmClientError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// A function the model may call. `parameters` is the JSON Schema of its
+/// arguments object.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tool {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+impl Tool {
+    pub fn new(name: &str, description: &str, parameters: Value) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+        }
+    }
+
+    /// The `{"type": "function", ...}` definition used by OpenAI and Ollama.
+    pub fn to_function_definition(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Tool>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, tool: Tool) -> Result<(), LlmClientError> {
+        if tool.name.is_empty() {
+            return Err(LlmClientError::InvalidInput(
+                "Tool name cannot be empty".to_string(),
+            ));
+        }
+
+        if tool.parameters.get("type").and_then(Value::as_str) != Some("object") {
+            return Err(LlmClientError::InvalidInput(format!(
+                "Parameters of tool {} must be a JSON Schema of type object",
+                tool.name
+            )));
+        }
+
+        if self.get(&tool.name).is_some() {
+            return Err(LlmClientError::InvalidInput(format!(
+                "Tool {} is already registered",
+                tool.name
+            )));
+        }
+
+        self.tools.push(tool);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Tool> {
+        self.tools.iter().find(|tool| tool.name == name)
+    }
+
+    pub fn tools(&self) -> &[Tool] {
+        &self.tools
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+}
@@ -0,0 +1,540 @@
+use crate::llm_client::{EmbedResponse, LlmClientError};
+use crate::text_splitter::Chunk;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum VectorStoreError {
+    DimensionMismatch(String),
+    InvalidInput(String),
+    ReadError(String),
+    WriteError(String),
+    ParseError(String),
+}
+
+impl Display for VectorStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VectorStoreError::DimensionMismatch(msg) => write!(f, "Dimension mismatch: {msg}"),
+            VectorStoreError::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
+            VectorStoreError::ReadError(msg) => write!(f, "Unable to read vector store: {msg}"),
+            VectorStoreError::WriteError(msg) => write!(f, "Unable to write vector store: {msg}"),
+            VectorStoreError::ParseError(msg) => write!(f, "Unable to parse vector store: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for VectorStoreError {}
+
+impl From<VectorStoreError> for LlmClientError {
+    fn from(error: VectorStoreError) -> Self {
+        match error {
+            VectorStoreError::InvalidInput(msg) => LlmClientError::InvalidInput(msg),
+            other => LlmClientError::VectorStore(other),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VectorRecord {
+    pub id: String,
+    pub vector: Vec<f32>,
+    #[serde(default)]
+    pub metadata: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Similarity {
+    #[default]
+    Cosine,
+    Dot,
+    L2,
+}
+
+impl Similarity {
+    fn score(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Similarity::Cosine => {
+                let norm = dot(a, a).sqrt() * dot(b, b).sqrt();
+                if norm == 0.0 { 0.0 } else { dot(a, b) / norm }
+            }
+            Similarity::Dot => dot(a, b),
+            Similarity::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+        }
+    }
+
+    // L2 is a distance, so smaller is better
+    fn compare(&self, a: f32, b: f32) -> Ordering {
+        match self {
+            Similarity::L2 => a.total_cmp(&b),
+            Similarity::Cosine | Similarity::Dot => b.total_cmp(&a),
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Equals(Value),
+    NotEquals(Value),
+    OneOf(Vec<Value>),
+    Exists,
+}
+
+/// Metadata conditions that must all hold for a record to be returned.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataFilter {
+    conditions: Vec<(String, Condition)>,
+}
+
+impl MetadataFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn eq(mut self, key: &str, value: Value) -> Self {
+        self.conditions
+            .push((key.to_string(), Condition::Equals(value)));
+        self
+    }
+
+    pub fn ne(mut self, key: &str, value: Value) -> Self {
+        self.conditions
+            .push((key.to_string(), Condition::NotEquals(value)));
+        self
+    }
+
+    pub fn one_of(mut self, key: &str, values: Vec<Value>) -> Self {
+        self.conditions
+            .push((key.to_string(), Condition::OneOf(values)));
+        self
+    }
+
+    pub fn exists(mut self, key: &str) -> Self {
+        self.conditions.push((key.to_string(), Condition::Exists));
+        self
+    }
+
+    pub fn matches(&self, metadata: &Map<String, Value>) -> bool {
+        self.conditions
+            .iter()
+            .all(|(key, condition)| match (condition, metadata.get(key)) {
+                (Condition::Equals(expected), Some(value)) => value == expected,
+                (Condition::NotEquals(expected), Some(value)) => value != expected,
+                (Condition::NotEquals(_), None) => true,
+                (Condition::OneOf(values), Some(value)) => values.contains(value),
+                (Condition::Exists, Some(_)) => true,
+                (_, None) => false,
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub id: String,
+    /// Similarity for cosine and dot (higher is closer), distance for L2 (lower is closer).
+    pub score: f32,
+    pub metadata: Map<String, Value>,
+}
+
+/// An in-memory vector index with brute-force top-k search.
+#[derive(Debug, Clone, Default)]
+pub struct VectorStore {
+    records: Vec<VectorRecord>,
+    positions: HashMap<String, usize>,
+}
+
+impl VectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn dimensions(&self) -> Option<usize> {
+        self.records.first().map(|record| record.vector.len())
+    }
+
+    pub fn records(&self) -> &[VectorRecord] {
+        &self.records
+    }
+
+    pub fn get(&self, id: &str) -> Option<&VectorRecord> {
+        self.positions.get(id).map(|i| &self.records[*i])
+    }
+
+    /// Adds a record, replacing any existing record with the same id.
+    pub fn insert(&mut self, record: VectorRecord) -> Result<(), VectorStoreError> {
+        if record.vector.is_empty() {
+            return Err(VectorStoreError::InvalidInput(format!(
+                "Record {} has an empty vector",
+                record.id
+            )));
+        }
+
+        if let Some(dimensions) = self.dimensions()
+            && dimensions != record.vector.len()
+        {
+            return Err(VectorStoreError::DimensionMismatch(format!(
+                "Record {} has {} dimensions, the store has {dimensions}",
+                record.id,
+                record.vector.len()
+            )));
+        }
+
+        match self.positions.get(&record.id) {
+            Some(i) => self.records[*i] = record,
+            None => {
+                self.positions.insert(record.id.clone(), self.records.len());
+                self.records.push(record);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add(
+        &mut self,
+        id: &str,
+        vector: Vec<f32>,
+        metadata: Map<String, Value>,
+    ) -> Result<(), VectorStoreError> {
+        self.insert(VectorRecord {
+            id: id.to_string(),
+            vector,
+            metadata,
+        })
+    }
+
+    /// Adds the vectors of an embeddings response, pairing them with `ids`
+    /// and `metadata` by position.
+    pub fn add_embeddings(
+        &mut self,
+        ids: &[String],
+        embeddings: EmbedResponse,
+        metadata: Vec<Map<String, Value>>,
+    ) -> Result<(), VectorStoreError> {
+        if ids.len() != embeddings.embeddings.len() || ids.len() != metadata.len() {
+            return Err(VectorStoreError::InvalidInput(format!(
+                "Got {} ids, {} embeddings and {} metadata entries",
+                ids.len(),
+                embeddings.embeddings.len(),
+                metadata.len()
+            )));
+        }
+
+        for ((id, vector), metadata) in ids.iter().zip(embeddings.embeddings).zip(metadata) {
+            self.add(id, vector, metadata)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds split chunks with their embeddings. The chunk text and offsets are
+    /// kept in the metadata under `text`, `start` and `end`.
+    pub fn add_chunks(
+        &mut self,
+        chunks: &[Chunk],
+        embeddings: EmbedResponse,
+    ) -> Result<(), VectorStoreError> {
+        let ids: Vec<String> = chunks.iter().map(|chunk| chunk.id.clone()).collect();
+        let metadata = chunks
+            .iter()
+            .map(|chunk| {
+                let mut metadata = chunk.metadata.clone();
+                metadata.insert("text".to_string(), Value::from(chunk.text.as_str()));
+                metadata.insert("start".to_string(), Value::from(chunk.start));
+                metadata.insert("end".to_string(), Value::from(chunk.end));
+                metadata
+            })
+            .collect();
+
+        self.add_embeddings(&ids, embeddings, metadata)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<VectorRecord> {
+        let position = self.positions.remove(id)?;
+        let record = self.records.swap_remove(position);
+
+        if let Some(moved) = self.records.get(position) {
+            self.positions.insert(moved.id.clone(), position);
+        }
+
+        Some(record)
+    }
+
+    /// The `k` records closest to `query`, best match first.
+    pub fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        similarity: Similarity,
+        filter: Option<&MetadataFilter>,
+    ) -> Result<Vec<SearchResult>, VectorStoreError> {
+        if let Some(dimensions) = self.dimensions()
+            && dimensions != query.len()
+        {
+            return Err(VectorStoreError::DimensionMismatch(format!(
+                "Query has {} dimensions, the store has {dimensions}",
+                query.len()
+            )));
+        }
+
+        let mut scored: Vec<(f32, &VectorRecord)> = self
+            .records
+            .iter()
+            .filter(|record| filter.is_none_or(|f| f.matches(&record.metadata)))
+            .map(|record| (similarity.score(query, &record.vector), record))
+            .collect();
+
+        scored.sort_by(|a, b| similarity.compare(a.0, b.0));
+        scored.truncate(k);
+
+        Ok(scored
+            .into_iter()
+            .map(|(score, record)| SearchResult {
+                id: record.id.clone(),
+                score,
+                metadata: record.metadata.clone(),
+            })
+            .collect())
+    }
+
+    /// Writes one JSON record per line.
+    pub fn save_jsonl(&self, path: &Path) -> Result<(), VectorStoreError> {
+        let file = File::create(path)
+            .map_err(|e| VectorStoreError::WriteError(format!("{}: {e}", path.display())))?;
+        let mut writer = BufWriter::new(file);
+
+        for record in &self.records {
+            let line = serde_json::to_string(record)
+                .map_err(|e| VectorStoreError::WriteError(e.to_string()))?;
+            writeln!(writer, "{line}")
+                .map_err(|e| VectorStoreError::WriteError(format!("{}: {e}", path.display())))?;
+        }
+
+        writer
+            .flush()
+            .map_err(|e| VectorStoreError::WriteError(format!("{}: {e}", path.display())))
+    }
+
+    pub fn load_jsonl(path: &Path) -> Result<Self, VectorStoreError> {
+        let file = File::open(path)
+            .map_err(|e| VectorStoreError::ReadError(format!("{}: {e}", path.display())))?;
+
+        let mut store = VectorStore::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line =
+                line.map_err(|e| VectorStoreError::ReadError(format!("{}: {e}", path.display())))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: VectorRecord = serde_json::from_str(&line).map_err(|e| {
+                VectorStoreError::ParseError(format!("{} line {}: {e}", path.display(), number + 1))
+            })?;
+            store.insert(record)?;
+        }
+
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        }
+    }
+
+    fn store() -> VectorStore {
+        let mut store = VectorStore::new();
+        for (id, vector, lang) in [
+            ("a", vec![10.0, 10.0], "en"),
+            ("b", vec![1.0, 0.0], "de"),
+            ("c", vec![3.0, -1.0], "en"),
+            ("d", vec![0.5, 0.0], "fr"),
+            ("e", vec![-0.2, 0.0], "en"),
+        ] {
+            store
+                .add(id, vector, metadata(json!({ "lang": lang })))
+                .unwrap();
+        }
+        store
+    }
+
+    fn ids(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|result| result.id.as_str()).collect()
+    }
+
+    #[test]
+    fn orders_by_similarity() {
+        let store = store();
+        let query = [1.0, 0.0];
+
+        let cosine = store.search(&query, 5, Similarity::Cosine, None).unwrap();
+        assert_eq!(ids(&cosine), ["b", "d", "c", "a", "e"]);
+        assert!((cosine[0].score - 1.0).abs() < 1e-6);
+
+        let dot = store.search(&query, 5, Similarity::Dot, None).unwrap();
+        assert_eq!(ids(&dot), ["a", "c", "b", "d", "e"]);
+
+        let l2 = store.search(&query, 3, Similarity::L2, None).unwrap();
+        assert_eq!(ids(&l2), ["b", "d", "e"]);
+        assert_eq!(l2[0].score, 0.0);
+    }
+
+    #[test]
+    fn checks_dimensions() {
+        let mut store = store();
+
+        assert!(matches!(
+            store.add("x", vec![1.0, 2.0, 3.0], Map::new()),
+            Err(VectorStoreError::DimensionMismatch(_))
+        ));
+        assert!(matches!(
+            store.add("x", Vec::new(), Map::new()),
+            Err(VectorStoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            store.search(&[1.0], 1, Similarity::Cosine, None),
+            Err(VectorStoreError::DimensionMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn insert_replaces_existing_ids() {
+        let mut store = store();
+        store.add("b", vec![0.0, 1.0], Map::new()).unwrap();
+
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.get("b").unwrap().vector, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn remove_keeps_positions_consistent() {
+        let mut store = store();
+
+        assert_eq!(store.remove("b").unwrap().id, "b");
+        assert!(store.remove("b").is_none());
+        assert_eq!(store.len(), 4);
+
+        // "e" was swapped into the removed slot
+        for id in ["a", "c", "d", "e"] {
+            assert_eq!(store.get(id).unwrap().id, id);
+        }
+        assert!(store.get("b").is_none());
+
+        store.remove("e").unwrap();
+        store.add("e", vec![2.0, 2.0], Map::new()).unwrap();
+        assert_eq!(store.get("e").unwrap().vector, [2.0, 2.0]);
+        assert_eq!(store.len(), 4);
+    }
+
+    #[test]
+    fn filters_by_metadata() {
+        let store = store();
+        let query = [1.0, 0.0];
+        let search = |filter: MetadataFilter| {
+            let results = store
+                .search(&query, 5, Similarity::Dot, Some(&filter))
+                .unwrap();
+            results.into_iter().map(|r| r.id).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            search(MetadataFilter::new().eq("lang", json!("en"))),
+            ["a", "c", "e"]
+        );
+        assert_eq!(
+            search(MetadataFilter::new().ne("lang", json!("en"))),
+            ["b", "d"]
+        );
+        assert_eq!(
+            search(MetadataFilter::new().one_of("lang", vec![json!("de"), json!("fr")])),
+            ["b", "d"]
+        );
+        assert!(search(MetadataFilter::new().exists("missing")).is_empty());
+        assert_eq!(
+            search(
+                MetadataFilter::new()
+                    .exists("lang")
+                    .eq("lang", json!("en"))
+                    .ne("missing", json!(1))
+            ),
+            ["a", "c", "e"]
+        );
+    }
+
+    #[test]
+    fn round_trips_jsonl() {
+        let store = store();
+        let path =
+            std::env::temp_dir().join(format!("vector-store-{}.jsonl", uuid::Uuid::new_v4()));
+
+        store.save_jsonl(&path).unwrap();
+        let loaded = VectorStore::load_jsonl(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.records(), store.records());
+        assert_eq!(loaded.get("c").unwrap().metadata["lang"], "en");
+        assert_eq!(loaded.dimensions(), Some(2));
+    }
+
+    #[test]
+    fn reports_bad_jsonl_lines() {
+        let path =
+            std::env::temp_dir().join(format!("vector-store-{}.jsonl", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "{\"id\":\"a\",\"vector\":[1.0]}\n\nnot json\n").unwrap();
+
+        let result = VectorStore::load_jsonl(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(VectorStoreError::ParseError(msg)) if msg.contains("line 3")));
+    }
+
+    #[test]
+    fn store_failures_keep_their_source() {
+        use std::error::Error;
+
+        let error = LlmClientError::from(VectorStoreError::ParseError("line 3".to_string()));
+        assert!(matches!(
+            error,
+            LlmClientError::VectorStore(VectorStoreError::ParseError(_))
+        ));
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            "Unable to parse vector store: line 3"
+        );
+
+        let error = LlmClientError::from(VectorStoreError::InvalidInput("empty".to_string()));
+        assert!(matches!(error, LlmClientError::InvalidInput(_)));
+    }
+}
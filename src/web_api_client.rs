@@ -1,9 +1,21 @@
-use log::info;
-use reqwest::Client;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use crate::concurrency_limiter::{ConcurrencyLimiter, ConcurrencyPermit};
+use crate::rate_limiter::{RateLimitConfig, RateLimiter};
+use crate::settings::ServerConfig;
+use crate::sse::{self, SseEvent};
+use crate::streaming::ByteStream;
+use crate::text_splitter::approximate_tokens;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use log::{debug, info, warn};
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
 use reqwest::redirect::Policy;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
 use serde_json::Value;
 use std::fmt::Display;
+use std::sync::Arc;
 use std::time::Duration;
 use url::Url;
 
@@ -15,9 +27,16 @@ pub enum WebApiClientError {
     HeaderCreationError(String),
     ClientCreationError(String),
     PostFailed(String),
+    GetFailed(String),
     InvalidApiKey(String),
     InvalidInput(String),
     ParseError(String),
+    /// The server could not be reached or did not answer in time.
+    ConnectionFailed(String),
+    /// The server answered with an error status.
+    Http(Box<HttpError>),
+    /// The request queue for the server was full or the wait for a free slot timed out.
+    Overloaded(String),
 }
 
 impl Display for WebApiClientError {
@@ -30,19 +49,254 @@ impl Display for WebApiClientError {
                 write!(f, "Client creation error: {msg}")
             }
             WebApiClientError::PostFailed(msg) => write!(f, "POST request failed: {msg}"),
+            WebApiClientError::GetFailed(msg) => write!(f, "GET request failed: {msg}"),
             WebApiClientError::InvalidApiKey(msg) => write!(f, "Invalid API key: {msg}"),
             WebApiClientError::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
             WebApiClientError::ParseError(msg) => write!(f, "Parse error: {msg}"),
+            WebApiClientError::ConnectionFailed(msg) => write!(f, "Connection failed: {msg}"),
+            WebApiClientError::Http(_) => write!(f, "Error response from server"),
+            WebApiClientError::Overloaded(msg) => write!(f, "Server overloaded: {msg}"),
         }
     }
 }
 
+impl std::error::Error for WebApiClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WebApiClientError::Http(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl WebApiClientError {
+    pub fn http(&self) -> Option<&HttpError> {
+        match self {
+            WebApiClientError::Http(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            WebApiClientError::ConnectionFailed(_) | WebApiClientError::Overloaded(_) => true,
+            WebApiClientError::Http(error) => error.is_retryable(),
+            _ => false,
+        }
+    }
+
+    pub fn is_auth(&self) -> bool {
+        match self {
+            WebApiClientError::InvalidApiKey(_) => true,
+            WebApiClientError::Http(error) => error.is_auth(),
+            _ => false,
+        }
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, WebApiClientError::Http(error) if error.is_rate_limited())
+    }
+}
+
+/// The error object of a failed response body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderError {
+    pub message: String,
+    /// `error.type` for OpenAI and Anthropic, `error.status` for Gemini.
+    pub kind: Option<String>,
+    pub code: Option<String>,
+}
+
+impl ProviderError {
+    /// Reads `{"error": {"message", "type", "code"}}` as sent by OpenAI, Azure,
+    /// Anthropic and Gemini, and Ollama's `{"error": "message"}`.
+    pub fn parse(body: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(body).ok()?;
+        let error = value.get("error")?;
+
+        if let Some(message) = error.as_str() {
+            return Some(Self {
+                message: message.to_string(),
+                kind: None,
+                code: None,
+            });
+        }
+
+        // codes are strings for OpenAI and numbers for Gemini
+        let field = |key: &str| match error.get(key)? {
+            Value::Null => None,
+            Value::String(text) => Some(text.clone()),
+            other => Some(other.to_string()),
+        };
+
+        Some(Self {
+            message: field("message").unwrap_or_default(),
+            kind: field("type").or_else(|| field("status")),
+            code: field("code"),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+    pub provider_error: Option<ProviderError>,
+}
+
+impl HttpError {
+    pub fn new(status: StatusCode, headers: HeaderMap, body: String) -> Self {
+        Self {
+            provider_error: ProviderError::parse(&body),
+            status,
+            headers,
+            body,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        is_retryable_status(self.status)
+    }
+
+    pub fn is_auth(&self) -> bool {
+        self.status == StatusCode::UNAUTHORIZED || self.status == StatusCode::FORBIDDEN
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// How long the server asked us to wait before trying again.
+    pub fn retry_after(&self) -> Option<Duration> {
+        retry_after(&self.headers)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.provider_error {
+            Some(error) if !error.message.is_empty() => {
+                write!(
+                    f,
+                    "Server returned error status {}: {}",
+                    self.status, error.message
+                )
+            }
+            _ => write!(
+                f,
+                "Server returned error status {}: {}",
+                self.status, self.body
+            ),
+        }
+    }
+}
+
+/// How often and how patiently a failed request is repeated. Connection
+/// errors, timeouts, 408, 429 and 5xx responses are retried.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first, so 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Randomize each backoff between half and all of its length, so clients
+    /// that failed together do not retry together.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The wait before retrying after failed attempt number `attempt`
+    /// (starting at 1). A server-provided `retry_after` takes precedence.
+    /// Either is capped at `max_delay_ms`.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(Duration::from_millis(self.max_delay_ms));
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay_ms
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay_ms);
+
+        if self.jitter && delay > 0 {
+            Duration::from_millis(rand::thread_rng().gen_range(delay / 2..=delay))
+        } else {
+            Duration::from_millis(delay)
+        }
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// Reads `retry-after-ms` (sent by OpenAI) or `Retry-After` as seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(ms) = headers
+        .get("retry-after-ms")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+    {
+        return Duration::try_from_secs_f64(ms.max(0.0) / 1000.0).ok();
+    }
+
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Appends `path` to `base`, keeping any path `base` already has, so
+/// `https://gateway/llm/` and `v1/models` give `https://gateway/llm/v1/models`.
+pub fn join_url(base: &Url, path: &str) -> Result<Url, WebApiClientError> {
+    let mut base = base.clone();
+    if !base.path().ends_with('/') {
+        let path = format!("{}/", base.path());
+        base.set_path(&path);
+    }
+
+    base.join(path.trim_start_matches('/'))
+        .map_err(|e| WebApiClientError::InvalidInput(format!("Invalid URL: {e}")))
+}
+
 #[derive(Debug)]
 pub struct WebApiClient {
     headers: HeaderMap,
     user_agent: String,
     connection_timeout: Option<u64>,
     deadline_timeout: Option<u64>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
     client: Client,
 }
 
@@ -60,6 +314,9 @@ impl WebApiClient {
             user_agent: format!("{APP_NAME} {APP_VERSION}"),
             connection_timeout,
             deadline_timeout,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            concurrency_limiter: None,
             client: Client::new(),
         };
         web_api_client.client = web_api_client
@@ -69,6 +326,28 @@ impl WebApiClient {
         web_api_client
     }
 
+    /// A client with the timeouts, retry policy and rate limit of `setting`.
+    /// Clients given the same `concurrency_limiter` share its limit.
+    pub fn for_server(
+        setting: &ServerConfig,
+        concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
+    ) -> Self {
+        debug!(
+            "Setting Connection Timeout: {}",
+            setting.connection_timeout.unwrap_or(u64::MAX)
+        );
+
+        debug!(
+            "Setting Deadline Timeout: {}",
+            setting.deadline_timeout.unwrap_or(u64::MAX)
+        );
+
+        Self::new(setting.connection_timeout, setting.deadline_timeout)
+            .with_retry_policy(setting.retry.clone())
+            .with_rate_limit(&setting.rate_limit)
+            .with_concurrency_limiter(concurrency_limiter)
+    }
+
     pub fn add_header(
         &mut self,
         key: &str,
@@ -88,6 +367,115 @@ impl WebApiClient {
         Ok(self)
     }
 
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limits requests to the budget in `config`, if it sets any.
+    pub fn with_rate_limit(mut self, config: &RateLimitConfig) -> Self {
+        self.rate_limiter = config
+            .is_enabled()
+            .then(|| Arc::new(RateLimiter::new(config)));
+        self
+    }
+
+    /// Holds a slot of `concurrency_limiter` for every request, see
+    /// `ConcurrencyLimiter::for_server`.
+    pub fn with_concurrency_limiter(
+        mut self,
+        concurrency_limiter: Option<Arc<ConcurrencyLimiter>>,
+    ) -> Self {
+        self.concurrency_limiter = concurrency_limiter;
+        self
+    }
+
+    pub fn concurrency_limiter(&self) -> Option<&Arc<ConcurrencyLimiter>> {
+        self.concurrency_limiter.as_ref()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    async fn acquire_slot(&self) -> Result<Option<ConcurrencyPermit>, WebApiClientError> {
+        match &self.concurrency_limiter {
+            Some(concurrency_limiter) => Ok(Some(concurrency_limiter.acquire().await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Sends the request built by `request`, rebuilding and resending it
+    /// while the failure is transient and attempts remain. A final retryable
+    /// response is returned as is for the caller to report. Every attempt
+    /// takes a concurrency slot and waits for the rate limit, counting about
+    /// `tokens` tokens. The slot of the returned response is handed back so
+    /// the caller can hold it while reading the body.
+    async fn send_with_retry(
+        &self,
+        method: &str,
+        tokens: usize,
+        request: impl Fn() -> RequestBuilder,
+        failed: fn(String) -> WebApiClientError,
+    ) -> Result<(Response, Option<ConcurrencyPermit>), WebApiClientError> {
+        let max_attempts = self.retry_policy.max_attempts.max(1);
+        let mut attempt = 1;
+
+        loop {
+            let slot = self.acquire_slot().await?;
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire(tokens).await;
+            }
+
+            // reqwest errors print the URL, which may carry credentials
+            let result = request().send().await.map_err(reqwest::Error::without_url);
+            if let (Some(rate_limiter), Ok(response)) = (&self.rate_limiter, &result) {
+                rate_limiter.update_from_headers(response.headers());
+            }
+
+            let wait = match result {
+                Ok(response) if !is_retryable_status(response.status()) => {
+                    return Ok((response, slot));
+                }
+                Ok(response) if attempt >= max_attempts => return Ok((response, slot)),
+                Ok(response) => {
+                    let wait = retry_after(response.headers());
+                    // not worth holding on for, the caller sees the wait in the error
+                    if wait.is_some_and(|wait| {
+                        wait > Duration::from_millis(self.retry_policy.max_delay_ms)
+                    }) {
+                        return Ok((response, slot));
+                    }
+
+                    warn!(
+                        "HTTP {method} attempt {attempt} returned {}",
+                        response.status()
+                    );
+                    wait
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    if attempt >= max_attempts {
+                        return Err(WebApiClientError::ConnectionFailed(format!(
+                            "HTTP {method} error: {e}"
+                        )));
+                    }
+                    warn!("HTTP {method} attempt {attempt} failed: {e}");
+                    None
+                }
+                Err(e) => return Err(failed(format!("HTTP {method} error: {e}"))),
+            };
+
+            // other requests may use the slot during the backoff
+            drop(slot);
+
+            let delay = self.retry_policy.delay(attempt, wait);
+            info!("Retrying HTTP {method} in {delay:?}");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
     fn get_client(&mut self) -> Result<Client, WebApiClientError> {
         let mut client_builder = Client::builder()
             .user_agent(self.user_agent.clone())
@@ -115,31 +503,449 @@ impl WebApiClient {
         url: Url,
         payload: &Value,
     ) -> Result<Value, WebApiClientError> {
-        let response = self
-            .client
-            .post(url)
-            .json(payload) // Send as JSON
-            .send()
-            .await
-            .map_err(|e| WebApiClientError::PostFailed(format!("HTTP POST error: {e}")))?;
+        let (response, _slot) = self
+            .send_with_retry(
+                "POST",
+                approximate_tokens(&payload.to_string()),
+                || self.client.post(url.clone()).json(payload), // Send as JSON
+                WebApiClientError::PostFailed,
+            )
+            .await?;
+
+        Self::read_json_response(response, WebApiClientError::PostFailed).await
+    }
+
+    /// Sends a POST and hands back the response body as it arrives.
+    pub async fn post_stream(
+        &self,
+        url: Url,
+        payload: &Value,
+    ) -> Result<ByteStream, WebApiClientError> {
+        let (response, slot) = self
+            .send_with_retry(
+                "POST",
+                approximate_tokens(&payload.to_string()),
+                || self.client.post(url.clone()).json(payload),
+                WebApiClientError::PostFailed,
+            )
+            .await?;
 
         let status = response.status();
+        info!("Response status: {status}");
+
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let text = response.text().await.unwrap_or_default();
+            return Err(WebApiClientError::Http(Box::new(HttpError::new(
+                status, headers, text,
+            ))));
+        }
+
+        // the slot is released when the stream is dropped
+        Ok(response
+            .bytes_stream()
+            .map(move |chunk| {
+                let _slot = &slot;
+                chunk.map_err(|e| {
+                    let e = e.without_url();
+                    WebApiClientError::PostFailed(format!("Error reading response stream: {e}"))
+                })
+            })
+            .boxed())
+    }
+
+    /// Sends a POST and reads the response as server-sent events.
+    pub async fn post_event_stream(
+        &self,
+        url: Url,
+        payload: &Value,
+    ) -> Result<BoxStream<'static, Result<SseEvent, WebApiClientError>>, WebApiClientError> {
+        let bytes = self.post_stream(url, payload).await?;
+        Ok(sse::events(bytes))
+    }
+
+    pub async fn get_request(&self, url: Url) -> Result<Value, WebApiClientError> {
+        let (response, _slot) = self
+            .send_with_retry(
+                "GET",
+                0,
+                || self.client.get(url.clone()),
+                WebApiClientError::GetFailed,
+            )
+            .await?;
+
+        Self::read_json_response(response, WebApiClientError::GetFailed).await
+    }
+
+    async fn read_json_response(
+        response: Response,
+        failed: fn(String) -> WebApiClientError,
+    ) -> Result<Value, WebApiClientError> {
+        let status = response.status();
+        let headers = response.headers().clone();
 
         let text = response.text().await.map_err(|e| {
-            WebApiClientError::PostFailed(format!("Error reading response body: {e}"))
+            let e = e.without_url();
+            let message = format!("Error reading response body: {e}");
+            if e.is_timeout() {
+                WebApiClientError::ConnectionFailed(message)
+            } else {
+                failed(message)
+            }
         })?;
 
         info!("Response status: {status}");
         // debug!("Response: {}", text);
 
         if !status.is_success() {
-            return Err(WebApiClientError::PostFailed(format!(
-                "Server returned error status {status}: {text}"
-            )));
+            return Err(WebApiClientError::Http(Box::new(HttpError::new(
+                status, headers, text,
+            ))));
         }
 
-        serde_json::from_str(&text).map_err(|e| {
-            WebApiClientError::PostFailed(format!("Failed to parse JSON response: {e}"))
-        })
+        serde_json::from_str(&text)
+            .map_err(|e| failed(format!("Failed to parse JSON response: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    /// Answers every request with `response` and counts the requests.
+    async fn serve(response: &'static str) -> (Url, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buffer[..n]),
+                    }
+                }
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn http_error(status: u16, body: &str) -> HttpError {
+        HttpError::new(
+            StatusCode::from_u16(status).unwrap(),
+            HeaderMap::new(),
+            body.to_string(),
+        )
+    }
+
+    #[test]
+    fn parses_openai_error_body() {
+        let body = r#"{"error": {"message": "Rate limit reached", "type": "requests", "code": "rate_limit_exceeded", "param": null}}"#;
+        assert_eq!(
+            ProviderError::parse(body),
+            Some(ProviderError {
+                message: "Rate limit reached".to_string(),
+                kind: Some("requests".to_string()),
+                code: Some("rate_limit_exceeded".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_ollama_error_body() {
+        assert_eq!(
+            ProviderError::parse(r#"{"error": "model 'llama9' not found"}"#),
+            Some(ProviderError {
+                message: "model 'llama9' not found".to_string(),
+                kind: None,
+                code: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_gemini_error_body() {
+        let body = r#"{"error": {"code": 400, "message": "API key not valid.", "status": "INVALID_ARGUMENT"}}"#;
+        assert_eq!(
+            ProviderError::parse(body),
+            Some(ProviderError {
+                message: "API key not valid.".to_string(),
+                kind: Some("INVALID_ARGUMENT".to_string()),
+                code: Some("400".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_bodies_without_error_object() {
+        assert_eq!(ProviderError::parse(""), None);
+        assert_eq!(ProviderError::parse("<html>Bad Gateway</html>"), None);
+        assert_eq!(ProviderError::parse(r#"{"detail": "nope"}"#), None);
+    }
+
+    #[test]
+    fn http_error_display_prefers_provider_message() {
+        let error = http_error(404, r#"{"error": {"message": "No such model"}}"#);
+        assert_eq!(
+            error.to_string(),
+            "Server returned error status 404 Not Found: No such model"
+        );
+
+        let error = http_error(502, "Bad Gateway");
+        assert_eq!(error.provider_error, None);
+        assert_eq!(
+            error.to_string(),
+            "Server returned error status 502 Bad Gateway: Bad Gateway"
+        );
+    }
+
+    #[test]
+    fn classifies_http_statuses() {
+        // (status, retryable, auth, rate limited)
+        let cases = [
+            (400, false, false, false),
+            (401, false, true, false),
+            (403, false, true, false),
+            (408, true, false, false),
+            (429, true, false, true),
+            (500, true, false, false),
+            (503, true, false, false),
+        ];
+
+        for (status, retryable, auth, rate_limited) in cases {
+            let error = WebApiClientError::Http(Box::new(http_error(status, "")));
+            assert_eq!(error.is_retryable(), retryable, "{status}");
+            assert_eq!(error.is_auth(), auth, "{status}");
+            assert_eq!(error.is_rate_limited(), rate_limited, "{status}");
+
+            let error = error.http().unwrap();
+            assert_eq!(error.is_retryable(), retryable, "{status}");
+            assert_eq!(error.is_auth(), auth, "{status}");
+            assert_eq!(error.is_rate_limited(), rate_limited, "{status}");
+        }
+    }
+
+    fn joined(base: &str, path: &str) -> String {
+        join_url(&Url::parse(base).unwrap(), path)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn joins_with_or_without_trailing_slash() {
+        assert_eq!(
+            joined("https://api.openai.com", "v1/models"),
+            "https://api.openai.com/v1/models"
+        );
+        assert_eq!(
+            joined("https://api.openai.com/", "v1/models"),
+            "https://api.openai.com/v1/models"
+        );
+    }
+
+    #[test]
+    fn keeps_gateway_path_prefix() {
+        assert_eq!(
+            joined(
+                "https://gateway.example.com/llm/openai",
+                "v1/chat/completions"
+            ),
+            "https://gateway.example.com/llm/openai/v1/chat/completions"
+        );
+        assert_eq!(
+            joined(
+                "https://gateway.example.com/llm/openai/",
+                "v1/chat/completions"
+            ),
+            "https://gateway.example.com/llm/openai/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn leading_slash_does_not_drop_the_prefix() {
+        assert_eq!(
+            joined("https://gateway.example.com/llm", "/api/chat"),
+            "https://gateway.example.com/llm/api/chat"
+        );
+        assert_eq!(
+            joined("http://localhost:11434", "/api/chat"),
+            "http://localhost:11434/api/chat"
+        );
+    }
+
+    #[test]
+    fn version_in_base_is_not_merged_with_the_path() {
+        // base_api_url is documented to leave the version out
+        assert_eq!(
+            joined("https://api.openai.com/v1", "v1/models"),
+            "https://api.openai.com/v1/v1/models"
+        );
+    }
+
+    #[test]
+    fn delay_grows_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+            jitter: false,
+        };
+
+        let delays: Vec<_> = (1..=6).map(|attempt| policy.delay(attempt, None)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1_000, 1_000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.delay(u32::MAX, None), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn jitter_stays_between_half_and_full_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 30_000,
+            jitter: true,
+        };
+
+        for _ in 0..100 {
+            let delay = policy.delay(3, None);
+            assert!(
+                (Duration::from_millis(200)..=Duration::from_millis(400)).contains(&delay),
+                "{delay:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn server_wait_takes_precedence_but_is_capped() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(3600))),
+            Duration::from_millis(policy.max_delay_ms)
+        );
+    }
+
+    #[test]
+    fn reads_retry_after_seconds_and_dates() {
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "7")])),
+            Some(Duration::from_secs(7))
+        );
+
+        let date = (Utc::now() + chrono::Duration::seconds(120)).to_rfc2822();
+        let wait = retry_after(&headers(&[("retry-after", &date)])).unwrap();
+        assert!(
+            (Duration::from_secs(110)..=Duration::from_secs(120)).contains(&wait),
+            "{wait:?}"
+        );
+
+        // a date in the past means no wait to follow
+        let date = (Utc::now() - chrono::Duration::seconds(120)).to_rfc2822();
+        assert_eq!(retry_after(&headers(&[("retry-after", &date)])), None);
+
+        assert_eq!(retry_after(&headers(&[("retry-after", "soon")])), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn retry_after_ms_takes_precedence() {
+        assert_eq!(
+            retry_after(&headers(&[
+                ("retry-after-ms", "1500"),
+                ("retry-after", "30")
+            ])),
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn bad_retry_after_values_do_not_panic() {
+        assert_eq!(retry_after(&headers(&[("retry-after", "-5")])), None);
+        assert_eq!(
+            retry_after(&headers(&[("retry-after-ms", "-250")])),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            retry_after(&headers(&[("retry-after-ms", "NaN")])),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers(&[("retry-after-ms", "inf")])), None);
+        assert_eq!(retry_after(&headers(&[("retry-after-ms", "1e300")])), None);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_server_wait_exceeds_max_delay() {
+        let (url, requests) = serve(
+            "HTTP/1.1 429 Too Many Requests\r\nretry-after: 120\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        )
+        .await;
+
+        let client = WebApiClient::new(Some(5), Some(5)).with_retry_policy(RetryPolicy {
+            max_attempts: 5,
+            base_delay_ms: 10,
+            max_delay_ms: 1_000,
+            jitter: false,
+        });
+
+        let start = std::time::Instant::now();
+        let error = client.get_request(url).await.unwrap_err();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(error.is_rate_limited());
+        assert_eq!(
+            error.http().unwrap().retry_after(),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_while_server_wait_is_short() {
+        let (url, requests) = serve(
+            "HTTP/1.1 503 Service Unavailable\r\nretry-after-ms: 10\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        )
+        .await;
+
+        let client = WebApiClient::new(Some(5), Some(5)).with_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms: 10,
+            max_delay_ms: 1_000,
+            jitter: false,
+        });
+
+        let error = client.get_request(url).await.unwrap_err();
+
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            error.http().unwrap().status,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
     }
 }